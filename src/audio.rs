@@ -0,0 +1,512 @@
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::blocking::{multipart, Body};
+use which::which;
+
+use crate::config::Config;
+
+/// Audio formats the transcription API accepts: the file extensions that map to each,
+/// the extension to upload under, and the MIME type to send.
+const AUDIO_FORMATS: &[(&[&str], &str, &str)] = &[
+    (&["wav"], "wav", "audio/wav"),
+    (&["mp3", "mpga", "mpeg"], "mp3", "audio/mpeg"),
+    (&["m4a", "mp4"], "m4a", "audio/mp4"),
+    (&["ogg", "oga", "opus"], "ogg", "audio/ogg"),
+    (&["flac"], "flac", "audio/flac"),
+    (&["webm"], "webm", "audio/webm"),
+];
+
+/// Called during an upload with the fraction of the audio sent so far, up to 1.0 once
+/// all of it has been handed to the connection.
+pub type SendProgress = Arc<dyn Fn(f32) + Send + Sync>;
+
+/// Builds the multipart `file` part for an audio file, with a file name and content type
+/// the API will recognize. The MIME type is inferred from the extension unless the config
+/// overrides it; unsupported extensions are rejected before uploading.
+pub fn file_part(
+    file_path: &str,
+    config: &Config,
+    progress: Option<SendProgress>,
+) -> Result<multipart::Part, String> {
+    let path = Path::new(file_path);
+    let (upload_extension, mime) = upload_format(path, config)?;
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("audio");
+
+    let (reader, len) = ProgressReader::open(path, progress)?;
+    multipart::Part::reader_with_length(reader, len)
+        .file_name(format!("{}.{}", stem, upload_extension))
+        .mime_str(mime)
+        .map_err(|e| format!("Invalid MIME type '{}': {}", mime, e))
+}
+
+/// Opens an audio file for APIs that take it as the whole request body, with the
+/// MIME type to send it as.
+pub fn file_body(
+    file_path: &str,
+    config: &Config,
+    progress: Option<SendProgress>,
+) -> Result<(Body, String), String> {
+    let path = Path::new(file_path);
+    let (_, mime) = upload_format(path, config)?;
+    let (reader, len) = ProgressReader::open(path, progress)?;
+    Ok((Body::sized(reader, len), mime.to_string()))
+}
+
+/// A file being uploaded, reporting each whole percent read to its `SendProgress`.
+struct ProgressReader {
+    file: File,
+    len: u64,
+    read: u64,
+    reported: u64,
+    progress: Option<SendProgress>,
+}
+
+impl ProgressReader {
+    /// Opens `path` and returns the reader with the file's length.
+    fn open(path: &Path, progress: Option<SendProgress>) -> Result<(ProgressReader, u64), String> {
+        let file = File::open(path).map_err(|e| format!("Failed to attach file {:?}: {}", path, e))?;
+        let len = file.metadata().map_err(|e| format!("Failed to attach file {:?}: {}", path, e))?.len();
+        Ok((ProgressReader { file, len, read: 0, reported: 0, progress }, len))
+    }
+}
+
+impl Read for ProgressReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.file.read(buf)?;
+        self.read += read as u64;
+        if let Some(progress) = &self.progress {
+            let percent = (self.read * 100).checked_div(self.len).unwrap_or(100);
+            if percent > self.reported {
+                self.reported = percent;
+                progress(self.read as f32 / self.len as f32);
+            }
+        }
+        Ok(read)
+    }
+}
+
+/// The extension to upload `path` under and its MIME type, from `AUDIO_FORMATS` and
+/// `mime_overrides`.
+fn upload_format<'a>(path: &Path, config: &'a Config) -> Result<(&'static str, &'a str), String> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+
+    let (_, upload_extension, mime) = AUDIO_FORMATS
+        .iter()
+        .find(|(extensions, _, _)| extensions.contains(&extension.as_str()))
+        .ok_or_else(|| {
+            format!(
+                "Error: Unsupported audio format '.{}'. Supported: {}",
+                extension,
+                supported_extensions().join(", ")
+            )
+        })?;
+    let mime = config
+        .mime_overrides
+        .get(&extension)
+        .map(String::as_str)
+        .unwrap_or(mime);
+    Ok((upload_extension, mime))
+}
+
+/// Transcodes `input` to 32 kbps mono Opus with `ffmpeg`, next to it with an `.ogg`
+/// extension, so long recordings fit under upload limits. The caller deletes the result.
+pub fn compress_audio(input: &Path) -> Result<PathBuf, String> {
+    if which("ffmpeg").is_err() {
+        return Err("ffmpeg not installed".to_string());
+    }
+
+    let output = input.with_extension("ogg");
+    let result = Command::new("ffmpeg")
+        .args(["-v", "error", "-y", "-i"])
+        .arg(input)
+        .args(["-vn", "-ac", "1", "-c:a", "libopus", "-b:a", "32k"])
+        .arg(&output)
+        .output()
+        .map_err(|e| format!("failed to run ffmpeg: {}", e))?;
+    if !result.status.success() {
+        let _ = std::fs::remove_file(&output);
+        return Err(format!(
+            "ffmpeg exited with {}: {}",
+            result.status,
+            String::from_utf8_lossy(&result.stderr).trim()
+        ));
+    }
+    Ok(output)
+}
+
+/// Transcodes `input` to 16 kHz mono 16-bit WAV with `ffmpeg`, the format Whisper works
+/// in, for a provider that rejected the original. Written next to it as
+/// `<name>_converted.wav`; the caller deletes it.
+pub fn convert_to_wav(input: &Path) -> Result<PathBuf, String> {
+    let stem = input.file_stem().and_then(|s| s.to_str()).unwrap_or("audio");
+    let output = input.with_file_name(format!("{}_converted.wav", stem));
+    transcode_to_wav(input, &output)?;
+    Ok(output)
+}
+
+/// Copies an audio file of the user's to the WAV file `output` to be transcribed like a
+/// recording. WAV files are copied as they are, anything else is converted with
+/// `convert_to_wav`'s settings. `input` itself is never modified.
+pub fn import_audio(input: &Path, output: &Path) -> Result<(), String> {
+    let is_wav = input
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("wav"));
+    // A mislabeled file is converted from whatever it really contains
+    if is_wav && check_container(input).is_ok() {
+        return std::fs::copy(input, output)
+            .map(|_| ())
+            .map_err(|e| format!("couldn't be copied ({})", e));
+    }
+    transcode_to_wav(input, output).map_err(|e| format!("couldn't be converted ({})", e))
+}
+
+/// Runs `ffmpeg` to write `input` to `output` as 16 kHz mono 16-bit WAV.
+fn transcode_to_wav(input: &Path, output: &Path) -> Result<(), String> {
+    if which("ffmpeg").is_err() {
+        return Err("ffmpeg not installed".to_string());
+    }
+
+    let result = Command::new("ffmpeg")
+        .args(["-v", "error", "-y", "-i"])
+        .arg(input)
+        .args(["-vn", "-ar", "16000", "-ac", "1", "-c:a", "pcm_s16le"])
+        .arg(output)
+        .output()
+        .map_err(|e| format!("failed to run ffmpeg: {}", e))?;
+    if !result.status.success() {
+        let _ = std::fs::remove_file(output);
+        return Err(format!(
+            "ffmpeg exited with {}: {}",
+            result.status,
+            String::from_utf8_lossy(&result.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Splits `input` into pieces of at most about `max_bytes` with `ffmpeg`, cutting in
+/// pauses where possible. The pieces are written next to it as `<name>_chunk_000.<ext>`,
+/// ... and returned in order; the caller deletes them.
+pub fn split_audio(input: &Path, max_bytes: u64) -> Result<Vec<PathBuf>, String> {
+    if which("ffmpeg").is_err() || which("ffprobe").is_err() {
+        return Err("ffmpeg/ffprobe not installed".to_string());
+    }
+
+    let size = std::fs::metadata(input).map_err(|e| e.to_string())?.len();
+    let total = probe_duration(input)?;
+    // Leave headroom for container overhead and uneven bitrates
+    let pieces = (size as f64 / (max_bytes as f64 * 0.9)).ceil().max(1.0);
+    let cuts = choose_cuts(total, total / pieces, &detect_pauses(input)?);
+
+    let stem = input.file_stem().and_then(|s| s.to_str()).unwrap_or("audio");
+    let extension = input.extension().and_then(|e| e.to_str()).unwrap_or("wav");
+    let pattern = input.with_file_name(format!("{}_chunk_%03d.{}", stem, extension));
+    let cut_list: Vec<String> = cuts.iter().map(|t| format!("{:.3}", t)).collect();
+
+    let result = Command::new("ffmpeg")
+        .args(["-v", "error", "-y", "-i"])
+        .arg(input)
+        .args(["-f", "segment", "-segment_times", &cut_list.join(",")])
+        .args(["-reset_timestamps", "1", "-c", "copy"])
+        .arg(&pattern)
+        .output()
+        .map_err(|e| format!("failed to run ffmpeg: {}", e))?;
+
+    let chunks: Vec<PathBuf> = (0..=cuts.len())
+        .map(|i| input.with_file_name(format!("{}_chunk_{:03}.{}", stem, i, extension)))
+        .filter(|path| path.exists())
+        .collect();
+    if !result.status.success() {
+        for chunk in &chunks {
+            let _ = std::fs::remove_file(chunk);
+        }
+        return Err(format!(
+            "ffmpeg exited with {}: {}",
+            result.status,
+            String::from_utf8_lossy(&result.stderr).trim()
+        ));
+    }
+    Ok(chunks)
+}
+
+/// Length of an audio file in seconds, from `ffprobe`.
+fn probe_duration(input: &Path) -> Result<f64, String> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-show_entries", "format=duration", "-of", "csv=p=0"])
+        .arg(input)
+        .output()
+        .map_err(|e| format!("failed to run ffprobe: {}", e))?;
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .map_err(|_| format!("ffprobe could not read the duration of {:?}", input))
+}
+
+/// Midpoints of the pauses in an audio file, in seconds, from ffmpeg's `silencedetect`.
+fn detect_pauses(input: &Path) -> Result<Vec<f64>, String> {
+    let output = Command::new("ffmpeg")
+        .args(["-hide_banner", "-nostats", "-i"])
+        .arg(input)
+        .args(["-af", "silencedetect=noise=-30dB:d=0.3", "-f", "null", "-"])
+        .output()
+        .map_err(|e| format!("failed to run ffmpeg: {}", e))?;
+
+    // Lines look like "[silencedetect @ 0x...] silence_end: 12.34 | silence_duration: 0.56"
+    let log = String::from_utf8_lossy(&output.stderr);
+    let mut pauses = Vec::new();
+    let mut start = None;
+    for line in log.lines() {
+        if let Some(value) = line.split("silence_start: ").nth(1) {
+            start = value.trim().parse::<f64>().ok();
+        } else if let Some(value) = line.split("silence_end: ").nth(1) {
+            let end = value.split('|').next().and_then(|v| v.trim().parse::<f64>().ok());
+            if let (Some(start), Some(end)) = (start.take(), end) {
+                pauses.push((start + end) / 2.0);
+            }
+        }
+    }
+    Ok(pauses)
+}
+
+/// Picks cut points so no piece is longer than `target` seconds, preferring the last pause
+/// in the second half of each piece and cutting mid-speech only when there is none.
+fn choose_cuts(total: f64, target: f64, pauses: &[f64]) -> Vec<f64> {
+    let mut cuts = Vec::new();
+    let mut previous = 0.0;
+    while total - previous > target {
+        let latest = previous + target;
+        let cut = pauses
+            .iter()
+            .copied()
+            .rev()
+            .find(|&pause| pause > previous + target / 2.0 && pause <= latest)
+            .unwrap_or(latest);
+        cuts.push(cut);
+        previous = cut;
+    }
+    cuts
+}
+
+/// All file extensions `file_part` accepts.
+pub fn supported_extensions() -> Vec<&'static str> {
+    AUDIO_FORMATS
+        .iter()
+        .flat_map(|(extensions, _, _)| extensions.iter().copied())
+        .collect()
+}
+
+/// Container an audio file's first bytes belong to, as the upload extension from
+/// `AUDIO_FORMATS`, e.g. "wav" for a RIFF/WAVE header.
+fn sniff_format(header: &[u8]) -> Option<&'static str> {
+    match header {
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => Some("wav"),
+        [b'I', b'D', b'3', ..] => Some("mp3"),
+        // MPEG audio frame sync
+        [0xFF, second, ..] if second & 0xE0 == 0xE0 => Some("mp3"),
+        [_, _, _, _, b'f', b't', b'y', b'p', ..] => Some("m4a"),
+        [b'O', b'g', b'g', b'S', ..] => Some("ogg"),
+        [b'f', b'L', b'a', b'C', ..] => Some("flac"),
+        [0x1A, 0x45, 0xDF, 0xA3, ..] => Some("webm"),
+        _ => None,
+    }
+}
+
+/// Checks that the file's contents match its extension, so a recorder writing the wrong
+/// container fails here instead of as "Invalid file format" after uploading.
+/// Extensions `file_part` doesn't know are left for it to reject.
+pub fn check_container(path: &Path) -> Result<(), String> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+    let expected = match AUDIO_FORMATS
+        .iter()
+        .find(|(extensions, _, _)| extensions.contains(&extension.as_str()))
+    {
+        Some((_, upload_extension, _)) => *upload_extension,
+        None => return Ok(()),
+    };
+
+    let mut header = Vec::with_capacity(12);
+    File::open(path)
+        .and_then(|file| file.take(12).read_to_end(&mut header))
+        .map_err(|e| format!("couldn't be read ({})", e))?;
+
+    match sniff_format(&header) {
+        Some(found) if found == expected => Ok(()),
+        Some(found) => Err(format!(
+            "is named .{} but contains {} audio",
+            extension,
+            found.to_uppercase()
+        )),
+        None => Err(format!("is named .{} but isn't a recognized audio file", extension)),
+    }
+}
+
+/// Format details read from a WAV file's header.
+#[derive(Clone, Copy, Debug)]
+pub struct WavInfo {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub bits_per_sample: u16,
+    /// Size of the sample data in bytes.
+    pub data_len: u64,
+    /// Position of the sample data in the file.
+    pub data_offset: u64,
+}
+
+impl WavInfo {
+    /// Playback length of the sample data.
+    pub fn duration(&self) -> Duration {
+        let bytes_per_second =
+            u64::from(self.sample_rate) * u64::from(self.channels) * u64::from(self.bits_per_sample / 8);
+        if bytes_per_second == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(self.data_len as f64 / bytes_per_second as f64)
+    }
+}
+
+/// Reads the format of a WAV file by walking its RIFF chunks, so extended `fmt ` chunks,
+/// `WAVE_FORMAT_EXTENSIBLE`, and extra metadata chunks (`LIST`, `bext`, ...) are handled.
+/// Recorders that are killed mid-write often leave a placeholder data size; in that case
+/// the data is assumed to run to the end of the file.
+pub fn read_wav_info(path: &Path) -> Result<WavInfo, String> {
+    let mut file = File::open(path).map_err(|e| format!("cannot open: {}", e))?;
+    let file_len = file.metadata().map_err(|e| e.to_string())?.len();
+
+    let mut riff = [0u8; 12];
+    file.read_exact(&mut riff)
+        .map_err(|_| "file is shorter than a RIFF header".to_string())?;
+    if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+        return Err("not a RIFF/WAVE file".to_string());
+    }
+
+    let mut format: Option<(u32, u16, u16)> = None;
+    loop {
+        let mut header = [0u8; 8];
+        if file.read_exact(&mut header).is_err() {
+            return Err("no data chunk found".to_string());
+        }
+        let id = &header[0..4];
+        let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+        let position = file.stream_position().map_err(|e| e.to_string())?;
+
+        match id {
+            b"fmt " => {
+                if size < 16 {
+                    return Err(format!("fmt chunk too small ({} bytes)", size));
+                }
+                let mut fmt = [0u8; 16];
+                file.read_exact(&mut fmt).map_err(|e| e.to_string())?;
+                let channels = u16::from_le_bytes([fmt[2], fmt[3]]);
+                let sample_rate = u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]);
+                let bits_per_sample = u16::from_le_bytes([fmt[14], fmt[15]]);
+                format = Some((sample_rate, channels, bits_per_sample));
+            }
+            b"data" => {
+                let (sample_rate, channels, bits_per_sample) =
+                    format.ok_or_else(|| "data chunk before fmt chunk".to_string())?;
+                let remaining = file_len.saturating_sub(position);
+                let data_len = if size == 0 || size == u32::MAX || u64::from(size) > remaining {
+                    remaining
+                } else {
+                    u64::from(size)
+                };
+                return Ok(WavInfo {
+                    sample_rate,
+                    channels,
+                    bits_per_sample,
+                    data_len,
+                    data_offset: position,
+                });
+            }
+            _ => {}
+        }
+
+        // Chunks are padded to an even length
+        let next = position + u64::from(size) + u64::from(size % 2);
+        file.seek(SeekFrom::Start(next)).map_err(|e| e.to_string())?;
+    }
+}
+
+/// Writes PCM sample data to `path` as a WAV file.
+pub fn write_wav(
+    path: &Path,
+    sample_rate: u32,
+    channels: u16,
+    bits_per_sample: u16,
+    data: &[u8],
+) -> Result<(), String> {
+    let data_len = u32::try_from(data.len()).map_err(|_| "recording too long for WAV".to_string())?;
+    let block_align = channels * (bits_per_sample / 8);
+    let byte_rate = sample_rate * u32::from(block_align);
+
+    let file = File::create(path).map_err(|e| format!("cannot create {:?}: {}", path, e))?;
+    let mut out = BufWriter::new(file);
+    let write = |out: &mut BufWriter<File>| -> std::io::Result<()> {
+        out.write_all(b"RIFF")?;
+        out.write_all(&(36 + data_len).to_le_bytes())?;
+        out.write_all(b"WAVEfmt ")?;
+        out.write_all(&16u32.to_le_bytes())?;
+        out.write_all(&1u16.to_le_bytes())?; // PCM
+        out.write_all(&channels.to_le_bytes())?;
+        out.write_all(&sample_rate.to_le_bytes())?;
+        out.write_all(&byte_rate.to_le_bytes())?;
+        out.write_all(&block_align.to_le_bytes())?;
+        out.write_all(&bits_per_sample.to_le_bytes())?;
+        out.write_all(b"data")?;
+        out.write_all(&data_len.to_le_bytes())?;
+        out.write_all(data)?;
+        out.flush()
+    };
+    write(&mut out).map_err(|e| format!("cannot write {:?}: {}", path, e))
+}
+
+/// Loudest sample of a 16-bit PCM WAV file, from 0.0 (silence) to 1.0 (full scale).
+pub fn peak_level(path: &Path, info: &WavInfo) -> Result<f32, String> {
+    peak_level_between(path, info, info.data_offset, info.data_offset + info.data_len)
+}
+
+/// Loudest sample between two file positions of a 16-bit PCM WAV file, e.g. the part
+/// a recorder wrote since the last check.
+pub fn peak_level_between(path: &Path, info: &WavInfo, start: u64, end: u64) -> Result<f32, String> {
+    if info.bits_per_sample != 16 {
+        return Err(format!("{}-bit samples are not supported", info.bits_per_sample));
+    }
+
+    // Keep reads aligned to whole samples
+    let start = start.max(info.data_offset);
+    let start = start - (start - info.data_offset) % 2;
+    let mut file = File::open(path).map_err(|e| format!("cannot open: {}", e))?;
+    file.seek(SeekFrom::Start(start)).map_err(|e| e.to_string())?;
+    let mut data = file.take(end.saturating_sub(start));
+
+    let mut peak: u16 = 0;
+    let mut buffer = [0u8; 8192];
+    loop {
+        let read = data.read(&mut buffer).map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        for sample in buffer[..read].chunks_exact(2) {
+            peak = peak.max(i16::from_le_bytes([sample[0], sample[1]]).unsigned_abs());
+        }
+    }
+
+    Ok(f32::from(peak) / 32768.0)
+}
@@ -0,0 +1,763 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use log::{info, warn};
+use serde::Deserialize;
+
+use crate::stt::{self, SttProvider};
+
+/// Provider that transcription requests are sent to by default, for display.
+pub const PROVIDER: &str = "OpenAI";
+
+/// Model used for transcription requests by default.
+pub const MODEL: &str = "gpt-4o-mini-transcribe";
+
+/// Default base URL of the OpenAI-compatible API.
+pub const OPENAI_BASE_URL: &str = "https://api.openai.com/v1";
+
+/// Path of the transcription endpoint below the API's base URL.
+pub const TRANSCRIPTIONS_PATH: &str = "/audio/transcriptions";
+
+/// Model used for translation requests when a provider doesn't name one. Only
+/// whisper models support OpenAI's translations endpoint.
+pub const TRANSLATION_MODEL: &str = "whisper-1";
+
+/// Recordings smaller than this many bytes are taken to have captured no audio: a WAV
+/// header and a few milliseconds at the default format.
+pub const MIN_RECORDING_BYTES: u64 = 4096;
+
+/// Instructions given to the refine tool, followed by the transcript.
+pub const DEFAULT_REFINE_PROMPT: &str = "Rephrase what was said, in original language and tone, to be as clear as possible. This is a conversation transcript, so naturally it will include redundancies, repetitions, words out of order and bad phrasing.";
+
+/// Price per minute of audio assumed for models without a published price, that of the
+/// default model.
+const DEFAULT_COST_PER_MINUTE: f64 = 0.003;
+
+/// Marks where the transcript goes in a refine prompt.
+const TRANSCRIPT_PLACEHOLDER: &str = "{transcript}";
+
+/// A hosted speech-to-text service, picking the `SttProvider` a provider's requests
+/// use and so its default URL, model and authentication.
+#[derive(Deserialize, Eq, PartialEq, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderKind {
+    /// OpenAI, or any server with an OpenAI-compatible API.
+    #[default]
+    #[serde(rename = "openai")]
+    OpenAi,
+    /// Groq's OpenAI-compatible Whisper API.
+    Groq,
+    /// Deepgram's `/listen` API, which takes the audio as the request body.
+    Deepgram,
+}
+
+impl ProviderKind {
+    /// The API providers of this kind are called with, which sets their defaults.
+    pub fn api(self) -> &'static dyn SttProvider {
+        match self {
+            ProviderKind::OpenAi => &stt::OpenAi,
+            ProviderKind::Groq => &stt::Groq,
+            ProviderKind::Deepgram => &stt::Deepgram,
+        }
+    }
+}
+
+/// A transcription endpoint that speaks OpenAI's `/audio/transcriptions` API, or
+/// another provider's API as set by `kind`.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Provider {
+    /// Name shown in the status line.
+    pub name: String,
+    #[serde(default)]
+    pub kind: ProviderKind,
+    pub url: String,
+    pub model: String,
+    /// Model for English translations when `translate` is on. Defaults to whisper-1.
+    pub translation_model: Option<String>,
+    /// Shell command that prints this provider's API key. Providers without one use
+    /// their kind's `key_env_var` if set, or else the main API key.
+    pub api_key_command: Option<String>,
+    /// Key read from `key_env_var` or `api_key_command` at startup.
+    #[serde(skip)]
+    pub api_key: Option<String>,
+}
+
+impl Default for Provider {
+    fn default() -> Self {
+        Provider::from_base_url(ProviderKind::OpenAi, OPENAI_BASE_URL)
+    }
+}
+
+impl Provider {
+    /// The `kind` of provider at `base_url`, e.g. `http://localhost:8080/v1`, named
+    /// after its host unless it is the hosted service itself.
+    pub fn from_base_url(kind: ProviderKind, base_url: &str) -> Provider {
+        let api = kind.api();
+        let base_url = base_url.trim_end_matches('/');
+        let name = if base_url == api.base_url() {
+            api.name().to_string()
+        } else {
+            reqwest::Url::parse(base_url)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_string))
+                .unwrap_or_else(|| base_url.to_string())
+        };
+        Provider {
+            name,
+            kind,
+            url: format!("{}{}", base_url, api.transcriptions_path()),
+            model: api.default_model().to_string(),
+            translation_model: None,
+            api_key_command: None,
+            api_key: None,
+        }
+    }
+
+    /// The API this provider is called with.
+    pub fn api(&self) -> &'static dyn SttProvider {
+        self.kind.api()
+    }
+
+    /// Whether requests need an API key. Only the hosted APIs do; self-hosted servers
+    /// are called without an `Authorization` header when there is no key.
+    pub fn requires_key(&self) -> bool {
+        self.url.starts_with(self.api().base_url())
+    }
+
+    /// The provider's `/audio/translations` endpoint, next to its transcription endpoint.
+    /// `None` if `url` isn't a `/transcriptions` endpoint.
+    pub fn translation_url(&self) -> Option<String> {
+        self.url
+            .strip_suffix("/transcriptions")
+            .map(|base| format!("{}/translations", base))
+    }
+
+    /// The provider's `/models` endpoint, a cheap authenticated request for checking the
+    /// API key. `None` if `url` isn't an `/audio/transcriptions` endpoint.
+    pub fn models_url(&self) -> Option<String> {
+        self.url
+            .strip_suffix(TRANSCRIPTIONS_PATH)
+            .map(|base| format!("{}/models", base))
+    }
+
+    /// Model to request English translations from.
+    pub fn translation_model(&self) -> &str {
+        self.translation_model.as_deref().unwrap_or(TRANSLATION_MODEL)
+    }
+}
+
+/// Output format requested from the transcription endpoint.
+#[derive(Deserialize, Eq, PartialEq, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseFormat {
+    #[default]
+    Text,
+    Json,
+    VerboseJson,
+    Srt,
+    Vtt,
+}
+
+impl ResponseFormat {
+    /// Value sent in the `response_format` form field.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ResponseFormat::Text => "text",
+            ResponseFormat::Json => "json",
+            ResponseFormat::VerboseJson => "verbose_json",
+            ResponseFormat::Srt => "srt",
+            ResponseFormat::Vtt => "vtt",
+        }
+    }
+
+    /// Whether the given model is known to accept this format.
+    /// OpenAI's gpt-4o transcription models only return `text` or `json`.
+    pub fn is_supported_by(self, model: &str) -> bool {
+        if model.starts_with("gpt-4o") {
+            matches!(self, ResponseFormat::Text | ResponseFormat::Json)
+        } else {
+            true
+        }
+    }
+}
+
+/// How transcripts are split up before being shown and copied.
+#[derive(Deserialize, Eq, PartialEq, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SegmentOutput {
+    /// Leave the transcript as returned by the API.
+    #[default]
+    None,
+    /// One sentence per line.
+    Sentences,
+}
+
+/// Where finished transcripts go.
+#[derive(Deserialize, Eq, PartialEq, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputMode {
+    /// Copy to the clipboard.
+    #[default]
+    Clipboard,
+    /// Type into the focused window.
+    Type,
+    /// Copy to the clipboard and type into the focused window.
+    Both,
+}
+
+impl OutputMode {
+    pub const ALL: [OutputMode; 3] = [OutputMode::Clipboard, OutputMode::Type, OutputMode::Both];
+
+    /// Name used in the config file.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OutputMode::Clipboard => "clipboard",
+            OutputMode::Type => "type",
+            OutputMode::Both => "both",
+        }
+    }
+}
+
+/// Color scheme of the windows.
+#[derive(Deserialize, Eq, PartialEq, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Theme {
+    /// Follow the desktop's preference, where it can be detected.
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::System, Theme::Light, Theme::Dark];
+
+    /// Name used in the config file.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Theme::System => "system",
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+        }
+    }
+}
+
+/// User configuration, read from `~/.config/whisper-to-input/config.toml`.
+/// Missing keys fall back to their defaults.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Config {
+    pub response_format: ResponseFormat,
+    /// The API key itself. The provider's key variable, e.g. `OPENAI_API_KEY`, takes
+    /// precedence over it.
+    pub api_key: Option<String>,
+    /// Shell command that prints the API key, e.g. `pass show openai`. Takes precedence
+    /// over `~/.config/whisper_api_key`, which is still used if the command fails.
+    pub api_key_command: Option<String>,
+    /// Service used when no `providers` are configured. Other than OpenAI, it replaces
+    /// the default `api_base_url` and `model` with its own.
+    pub provider: ProviderKind,
+    /// Base URL of the OpenAI-compatible API used when no `providers` are configured,
+    /// e.g. `http://localhost:8080/v1` for a local whisper.cpp server.
+    pub api_base_url: String,
+    /// Transcription model used when no `providers` are configured, e.g. `whisper-1`.
+    pub model: String,
+    /// Largest audio file that will be uploaded. OpenAI rejects anything over 25 MB.
+    pub max_upload_bytes: u64,
+    /// Recordings smaller than this are reported as capturing no audio instead of
+    /// being uploaded.
+    pub min_recording_bytes: u64,
+    /// Recordings larger than this are compressed to Opus with `ffmpeg` before uploading.
+    /// 0 always uploads the WAV.
+    pub compress_above_bytes: u64,
+    /// Seconds to count down before recording starts. 0 starts immediately.
+    pub countdown_seconds: u32,
+    /// Ignore Record presses this many milliseconds after the last accepted one, so a
+    /// bouncing key can't stop a recording before the recorder has started. 0 disables.
+    pub record_debounce_ms: u64,
+    /// Longest time to wait for the recorder to exit and finish writing its file after
+    /// it is stopped. Raise it on slow disks if recordings come out truncated.
+    pub stop_grace_ms: u64,
+    /// Transcriptions that may run at the same time. Results are still delivered in the
+    /// order they were recorded.
+    pub max_parallel_transcriptions: u32,
+    /// Recordings that may wait for or be in transcription before Record is ignored.
+    /// 0 ignores Record until the current transcription is done.
+    pub max_queued_recordings: u32,
+    /// Stop recording automatically after this many seconds below `silence_db`.
+    /// 0 disables it.
+    pub silence_seconds: u32,
+    /// Level in dBFS below which the input counts as silent for `silence_seconds`.
+    pub silence_db: f32,
+    /// Stop recording automatically after this many seconds. 0 records until stopped.
+    pub max_recording_seconds: u32,
+    /// Sample rate of recordings in Hz. Whisper resamples to 16 kHz itself, so more only
+    /// makes uploads bigger.
+    pub sample_rate: u32,
+    /// 1 for mono or 2 for stereo.
+    pub channels: u16,
+    /// Keep listening in the background and start each recording with this many seconds
+    /// of audio from before Record was pressed. 0 disables it.
+    pub prebuffer_seconds: u32,
+    /// Beep when recording starts and a lower beep when it stops.
+    pub sound_cues: bool,
+    /// Offer the Refine step. Disable to run as a transcription-only tool even when
+    /// `ask` is installed.
+    pub refine: bool,
+    /// Shell command line to refine with instead of `ask`, e.g. `ssh workstation ask`.
+    /// The prompt and transcript are written to its stdin.
+    pub refine_command: Option<String>,
+    /// Pass the prompt to the refine tool as its last argument instead of on stdin, for
+    /// tools that don't read stdin. Long transcripts can exceed the argument length limit.
+    pub refine_prompt_as_argument: bool,
+    /// Instructions for the refine tool when no language-specific prompt applies. The
+    /// transcript replaces `{transcript}`, or follows the prompt if it has none.
+    pub refine_prompt: String,
+    /// Refine instructions per dictation language, keyed like `language`.
+    pub refine_prompts: HashMap<String, String>,
+    /// Language being dictated, as an ISO-639-1 code such as "pt". Sent to the API instead
+    /// of letting it detect the language, and picks the matching entry of `refine_prompts`.
+    pub language: Option<String>,
+    /// Text sent as Whisper's `prompt`, to bias the spelling of names and jargon.
+    pub prompt: Option<String>,
+    /// Show the transcript as it comes in, with models that can stream it.
+    pub stream_transcripts: bool,
+    /// Let the API detect the language, and only when that returns an empty or near-empty
+    /// transcript for a recording that isn't silent, transcribe it again with `language`.
+    pub retry_with_language: bool,
+    /// Also translate each recording to English and show the translation below the
+    /// transcript. Costs a second upload per recording.
+    pub translate: bool,
+    /// Add each new transcript to the end of the current one instead of replacing it.
+    /// Sets the initial state of the Append checkbox.
+    pub append_transcripts: bool,
+    /// When appending, copy the whole text each time instead of only the new part.
+    pub append_copy_whole: bool,
+    /// Inserted between appended transcripts, e.g. "\n- " for a bullet list.
+    pub append_separator: String,
+    /// Show a word diff of refinements and wait for Accept/Reject instead of applying them.
+    pub refine_diff: bool,
+    /// PulseAudio/PipeWire source description to record from, e.g. "Yeti Stereo Microphone".
+    /// Resolved with `pactl list sources` and recorded with `parecord`.
+    pub input_source_description: Option<String>,
+    /// Clear the transcript from the window this many seconds after it is copied, so
+    /// dictation doesn't linger on screen. 0 keeps it.
+    pub clear_transcript_after_seconds: u32,
+    /// Copy transcripts to the clipboard, type them into the focused window, or both.
+    pub output_mode: OutputMode,
+    /// With `output_mode = "clipboard"`, press Ctrl+V in the focused window after copying.
+    pub auto_paste: bool,
+    /// Wait before pasting, so focus has settled on the target window.
+    pub auto_paste_delay_ms: u64,
+    /// Light or dark windows, or whatever the desktop prefers.
+    pub theme: Theme,
+    /// Smallest width the main window can be resized to, in logical pixels.
+    pub min_width: u32,
+    /// Smallest height the main window can be resized to, in logical pixels.
+    pub min_height: u32,
+    /// Scales the status and transcript text, e.g. 1.5 on a HiDPI display.
+    pub ui_scale: f32,
+    /// Transcripts kept in `~/.local/share/whisper-to-input/history.jsonl` and listed under
+    /// History; the oldest are dropped beyond this. 0 keeps no history.
+    pub history_entries: u32,
+    /// API price per minute of audio, for the cost shown under the transcript. 0 hides it.
+    /// Unset uses the published price of the provider and model that transcribed it.
+    pub cost_per_minute: Option<f64>,
+    /// Save the last transcript to disk and restore it on startup.
+    pub persist_transcript: bool,
+    /// Move recordings to `~/.local/share/whisper-to-input/recordings` after a successful
+    /// transcription instead of deleting them.
+    pub keep_recordings: bool,
+    /// Keep the last transcribed recording until the next one, so Re-transcribe can send
+    /// it again, e.g. with another model or language.
+    pub keep_last_recording: bool,
+    /// Name kept recordings after their capture time and model, and tag them with the
+    /// device and the transcript's first line if `ffmpeg` is installed.
+    pub recording_metadata: bool,
+    /// Move transcribed recordings into this directory, named after their capture time,
+    /// instead of deleting them. Takes precedence over `keep_recordings`.
+    pub save_recordings_dir: Option<PathBuf>,
+    /// Global (X11) hotkey that copies the current transcript again, e.g. "Ctrl+Alt+C".
+    pub copy_hotkey: Option<String>,
+    /// Global (X11) hotkey that acts like the Record button, e.g. "Super+R".
+    #[serde(alias = "hotkey")]
+    pub record_hotkey: Option<String>,
+    /// Make `record_hotkey` record only while it is held, instead of toggling.
+    pub push_to_talk: bool,
+    /// Show a tray icon to record from, and keep running in the tray when the window
+    /// is closed.
+    pub tray: bool,
+    /// Refuse to start a second instance, which would fight over the microphone, and
+    /// show the running one's window instead.
+    pub single_instance: bool,
+    /// MIME types to send for audio file extensions, overriding the built-in mapping,
+    /// e.g. `m4a = "audio/x-m4a"` for servers that expect a non-standard type.
+    pub mime_overrides: HashMap<String, String>,
+    /// Show a desktop notification when a transcription finishes or fails while the
+    /// window is minimized or hidden.
+    pub notifications: bool,
+    /// Log the raw API response and offer to show it below the transcript.
+    pub debug_responses: bool,
+    /// How many times to retry a failed request to a provider before moving on.
+    pub max_retries: u32,
+    /// Wait before the first retry; doubled for each one after it.
+    pub retry_backoff_secs: f64,
+    /// Randomly shorten each wait by up to half, so clients don't retry in lockstep.
+    pub retry_jitter: bool,
+    /// Give up on a request that takes longer than this, upload included.
+    pub request_timeout_secs: u64,
+    /// Proxy for all API requests, e.g. `http://proxy.example.com:3128`. Without it the
+    /// `HTTPS_PROXY`, `HTTP_PROXY` and `ALL_PROXY` environment variables are used;
+    /// `NO_PROXY` applies either way.
+    pub proxy: Option<String>,
+    /// After this many transcriptions fail in a row, assume the API is down and skip
+    /// uploading until the user retries. 0 disables the circuit breaker.
+    pub circuit_breaker_failures: u32,
+    /// How to split the transcript before it is shown and copied.
+    pub segment_output: SegmentOutput,
+    /// Remove a single trailing newline, which the `text` format ends with.
+    pub strip_trailing_newline: bool,
+    /// Remove leading and trailing whitespace.
+    pub trim_whitespace: bool,
+    /// Replace runs of spaces and tabs with one space.
+    pub collapse_spaces: bool,
+    /// Capitalize the first letter of each sentence.
+    pub capitalize_sentences: bool,
+    /// Ask for word-level timestamps, which needs `verbose_json` (whisper-1), and save
+    /// them with each transcript for export. The window still shows plain text.
+    pub timestamps: bool,
+    /// Providers to try in order; later ones are fallbacks used when the earlier ones
+    /// fail. Never empty: defaults to the API at `api_base_url`.
+    #[serde(default)]
+    pub providers: Vec<Provider>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            response_format: ResponseFormat::default(),
+            api_key: None,
+            api_key_command: None,
+            provider: ProviderKind::OpenAi,
+            api_base_url: OPENAI_BASE_URL.to_string(),
+            model: MODEL.to_string(),
+            max_upload_bytes: 25 * 1024 * 1024,
+            min_recording_bytes: MIN_RECORDING_BYTES,
+            compress_above_bytes: 10 * 1024 * 1024,
+            countdown_seconds: 0,
+            record_debounce_ms: 300,
+            stop_grace_ms: 2000,
+            max_parallel_transcriptions: 1,
+            max_queued_recordings: 3,
+            silence_seconds: 0,
+            silence_db: -40.0,
+            max_recording_seconds: 0,
+            prebuffer_seconds: 0,
+            sound_cues: false,
+            sample_rate: 16000,
+            channels: 1,
+            refine: true,
+            refine_command: None,
+            refine_prompt_as_argument: false,
+            refine_prompt: DEFAULT_REFINE_PROMPT.to_string(),
+            refine_prompts: HashMap::new(),
+            language: None,
+            prompt: None,
+            stream_transcripts: true,
+            retry_with_language: false,
+            translate: false,
+            append_transcripts: false,
+            append_copy_whole: true,
+            append_separator: " ".to_string(),
+            refine_diff: false,
+            input_source_description: None,
+            clear_transcript_after_seconds: 0,
+            output_mode: OutputMode::Clipboard,
+            auto_paste: false,
+            auto_paste_delay_ms: 150,
+            theme: Theme::System,
+            min_width: 640,
+            min_height: 480,
+            ui_scale: 1.0,
+            history_entries: 100,
+            cost_per_minute: None,
+            persist_transcript: true,
+            keep_recordings: false,
+            keep_last_recording: false,
+            recording_metadata: false,
+            save_recordings_dir: None,
+            copy_hotkey: None,
+            record_hotkey: None,
+            push_to_talk: false,
+            tray: false,
+            single_instance: true,
+            mime_overrides: HashMap::new(),
+            notifications: true,
+            debug_responses: false,
+            max_retries: 2,
+            retry_backoff_secs: 2.0,
+            retry_jitter: false,
+            request_timeout_secs: 120,
+            proxy: None,
+            circuit_breaker_failures: 0,
+            segment_output: SegmentOutput::None,
+            strip_trailing_newline: false,
+            trim_whitespace: false,
+            collapse_spaces: false,
+            capitalize_sentences: false,
+            timestamps: false,
+            providers: vec![Provider::default()],
+        }
+    }
+}
+
+impl Config {
+    /// The provider tried first, shown in the idle status.
+    pub fn primary_provider(&self) -> &Provider {
+        &self.providers[0]
+    }
+
+    /// Price per minute of audio transcribed by a `kind` provider with `model`:
+    /// `cost_per_minute` if set, otherwise the published price, or else that of the
+    /// default model.
+    pub fn cost_per_minute_of(&self, kind: ProviderKind, model: &str) -> f64 {
+        self.cost_per_minute
+            .or_else(|| kind.api().price_per_minute(model))
+            .unwrap_or(DEFAULT_COST_PER_MINUTE)
+    }
+
+    /// Refine instructions for the current `language`, falling back to `refine_prompt`.
+    pub fn refine_prompt_for_language(&self) -> &str {
+        self.language
+            .as_ref()
+            .and_then(|language| self.refine_prompts.get(&language.to_lowercase()))
+            .unwrap_or(&self.refine_prompt)
+    }
+
+    /// Normalizes a language code typed by the user: `None` when empty or not an
+    /// ISO-639 code such as "pt".
+    pub fn parse_language(text: &str) -> Option<String> {
+        let code = text.trim().to_lowercase();
+        ((2..=3).contains(&code.len()) && code.chars().all(|c| c.is_ascii_lowercase()))
+            .then_some(code)
+    }
+
+    /// What the refine tool is given: the refine prompt with `{transcript}` replaced by the
+    /// transcript, or followed by it if the prompt has no placeholder.
+    pub fn refine_input(&self, transcript: &str) -> String {
+        let prompt = self.refine_prompt_for_language();
+        if prompt.contains(TRANSCRIPT_PLACEHOLDER) {
+            prompt.replace(TRANSCRIPT_PLACEHOLDER, transcript)
+        } else {
+            format!("{}\n\n{}", prompt, transcript)
+        }
+    }
+
+    /// Format to request: `verbose_json` when `timestamps` is on, since only it carries
+    /// them, otherwise `response_format`.
+    pub fn request_format(&self) -> ResponseFormat {
+        if self.timestamps {
+            ResponseFormat::VerboseJson
+        } else {
+            self.response_format
+        }
+    }
+
+    /// Whether the providers are the one derived from `api_base_url` and `model`, rather
+    /// than configured as [[providers]].
+    pub fn uses_base_url_provider(&self) -> bool {
+        match self.providers.as_slice() {
+            [provider] => {
+                provider.url == Provider::from_base_url(self.provider, &self.api_base_url).url
+                    && provider.model == self.model.trim()
+            }
+            _ => false,
+        }
+    }
+
+    /// Location of the config file, if a config directory can be determined.
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|p| p.join("whisper-to-input").join("config.toml"))
+    }
+
+    /// Loads the config file, falling back to defaults if it is missing or invalid, and
+    /// applies the environment overrides (see `apply_env_overrides`).
+    pub fn load() -> Config {
+        let mut config = Config::read_file().unwrap_or_else(|| Config {
+            providers: Vec::new(),
+            ..Config::default()
+        });
+        config.apply_env_overrides();
+
+        if let Some(language) = config.language.take() {
+            config.language = Config::parse_language(&language);
+            if config.language.is_none() {
+                warn!("Ignoring language '{}'; expected a code like \"pt\".", language);
+            }
+        }
+        config.prompt = config.prompt.filter(|prompt| !prompt.trim().is_empty());
+        if !(8000..=192000).contains(&config.sample_rate) {
+            warn!("Ignoring sample_rate {}; expected 8000 to 192000.", config.sample_rate);
+            config.sample_rate = 16000;
+        }
+        if !(1..=2).contains(&config.channels) {
+            warn!("Ignoring channels {}; expected 1 or 2.", config.channels);
+            config.channels = 1;
+        }
+        if !(0.5..=4.0).contains(&config.ui_scale) {
+            warn!("Ignoring ui_scale {}; expected 0.5 to 4.", config.ui_scale);
+            config.ui_scale = 1.0;
+        }
+        if config.model.trim().is_empty() {
+            warn!("model is empty; using {}.", config.provider.api().default_model());
+            config.model = config.provider.api().default_model().to_string();
+        }
+        // Left at OpenAI's defaults, the URL and model follow the chosen provider
+        if config.api_base_url.trim_end_matches('/') == OPENAI_BASE_URL {
+            config.api_base_url = config.provider.api().base_url().to_string();
+        }
+        if config.model == MODEL {
+            config.model = config.provider.api().default_model().to_string();
+        }
+        if config.providers.is_empty() {
+            let mut provider = Provider::from_base_url(config.provider, &config.api_base_url);
+            provider.model = config.model.trim().to_string();
+            config.providers.push(provider);
+        }
+        config
+    }
+
+    /// Parses the config file. `None` if there is none or it is invalid; `providers`
+    /// is left empty unless the file configures some.
+    fn read_file() -> Option<Config> {
+        let path = Config::path()?;
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to read config from {:?}: {}", path, e);
+                }
+                return None;
+            }
+        };
+
+        match toml::from_str::<Config>(&contents) {
+            Ok(config) => {
+                info!("Loaded config from {:?}", path);
+                Some(config)
+            }
+            Err(e) => {
+                warn!("Invalid config in {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    /// Lets the environment override the config file, for trying out a setting or
+    /// scripting without editing it: `OPENAI_BASE_URL` sets `api_base_url`, and
+    /// `WHISPER_TO_INPUT_MODEL` and `WHISPER_TO_INPUT_LANGUAGE` set `model` and `language`.
+    /// API keys such as `OPENAI_API_KEY` are handled with the other API key sources.
+    fn apply_env_overrides(&mut self) {
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        if let Some(url) = var("OPENAI_BASE_URL") {
+            self.api_base_url = url;
+        }
+        if let Some(model) = var("WHISPER_TO_INPUT_MODEL") {
+            self.model = model;
+        }
+        if let Some(language) = var("WHISPER_TO_INPUT_LANGUAGE") {
+            self.language = Some(language);
+        }
+    }
+
+    /// Writes the settings changed in the Settings window to the config file, keeping
+    /// everything else in it, comments included. Settings that weren't changed keep
+    /// the file's values, so environment overrides are never written to it.
+    pub fn save(changes: &SettingsChanges) -> Result<(), String> {
+        let path = Config::path().ok_or("No config directory")?;
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("Failed to read {:?}: {}", path, e)),
+        };
+        let contents = changes
+            .apply(&contents)
+            .map_err(|e| format!("Invalid config in {:?}: {}", path, e))?;
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+        }
+        std::fs::write(&path, contents).map_err(|e| format!("Failed to write {:?}: {}", path, e))?;
+        info!("Saved config to {:?}", path);
+        Ok(())
+    }
+}
+
+/// Settings the user changed in the Settings window, for `Config::save`. `None` leaves
+/// a setting as it is in the config file.
+#[derive(Default, Debug)]
+pub struct SettingsChanges {
+    pub model: Option<String>,
+    pub api_base_url: Option<String>,
+    pub output_mode: Option<OutputMode>,
+    pub theme: Option<Theme>,
+    /// `Some(None)` removes `language`, so it is detected again.
+    pub language: Option<Option<String>>,
+}
+
+impl SettingsChanges {
+    /// The config file `contents` with the changes made.
+    fn apply(&self, contents: &str) -> Result<String, toml_edit::TomlError> {
+        let mut document = contents.parse::<toml_edit::DocumentMut>()?;
+        if let Some(model) = &self.model {
+            document["model"] = toml_edit::value(model);
+        }
+        if let Some(url) = &self.api_base_url {
+            document["api_base_url"] = toml_edit::value(url);
+        }
+        if let Some(output_mode) = self.output_mode {
+            document["output_mode"] = toml_edit::value(output_mode.as_str());
+        }
+        if let Some(theme) = self.theme {
+            document["theme"] = toml_edit::value(theme.as_str());
+        }
+        match &self.language {
+            Some(Some(language)) => document["language"] = toml_edit::value(language),
+            Some(None) => {
+                document.remove("language");
+            }
+            None => {}
+        }
+        Ok(document.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "# Transcription model\nmodel = \"whisper-1\"\nlanguage = \"pt\"\n";
+
+    #[test]
+    fn unchanged_settings_keep_the_file_values() {
+        let changes = SettingsChanges { theme: Some(Theme::Dark), ..SettingsChanges::default() };
+        let saved = changes.apply(CONFIG).unwrap();
+        assert_eq!(saved, format!("{}theme = \"dark\"\n", CONFIG));
+    }
+
+    #[test]
+    fn changed_settings_are_written() {
+        let changes = SettingsChanges {
+            model: Some("gpt-4o-transcribe".to_string()),
+            language: Some(None),
+            ..SettingsChanges::default()
+        };
+        let saved = changes.apply(CONFIG).unwrap();
+        assert_eq!(saved, "# Transcription model\nmodel = \"gpt-4o-transcribe\"\n");
+    }
+}
@@ -0,0 +1,191 @@
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::Write;
+#[cfg(unix)]
+use std::io::{BufRead, BufReader, Read};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+#[cfg(unix)]
+use std::thread;
+
+#[cfg(unix)]
+use log::{debug, warn};
+use log::{error, info};
+
+/// A connection to the control socket, which the reply to a command is written to.
+#[cfg(unix)]
+pub type Stream = UnixStream;
+
+/// There is no control socket on Windows, so there are never any connections.
+#[cfg(not(unix))]
+pub enum Stream {}
+
+#[cfg(not(unix))]
+impl Write for Stream {
+    fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+        match *self {}
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match *self {}
+    }
+}
+
+/// A request sent to the running instance over its control socket.
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum ControlCommand {
+    /// Stop recording, finish any in-flight transcription or refinement, reply with
+    /// the final transcript and exit.
+    FinishAndQuit,
+    /// Same as pressing Record: start recording, or stop and transcribe.
+    ToggleRecording,
+    /// Show and raise the window, e.g. when a second instance is started.
+    Show,
+}
+
+impl ControlCommand {
+    fn as_str(self) -> &'static str {
+        match self {
+            ControlCommand::FinishAndQuit => "finish-and-quit",
+            ControlCommand::ToggleRecording => "toggle-recording",
+            ControlCommand::Show => "show",
+        }
+    }
+
+    #[cfg(unix)]
+    fn parse(text: &str) -> Option<ControlCommand> {
+        match text {
+            "finish-and-quit" => Some(ControlCommand::FinishAndQuit),
+            "toggle-recording" => Some(ControlCommand::ToggleRecording),
+            "show" => Some(ControlCommand::Show),
+            _ => None,
+        }
+    }
+}
+
+/// Path of the control socket, e.g. `/run/user/1000/whisper-to-input.sock`.
+pub fn socket_path() -> PathBuf {
+    dirs::runtime_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("whisper-to-input.sock")
+}
+
+/// Held by the running instance for `single_instance`; released when dropped.
+pub struct InstanceLock {
+    _file: File,
+}
+
+/// Takes the single-instance lock, `$XDG_RUNTIME_DIR/whisper-to-input.lock`.
+/// `Ok(None)` if another instance holds it.
+pub fn lock_instance() -> Result<Option<InstanceLock>, String> {
+    let path = socket_path().with_extension("lock");
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .map_err(|e| format!("cannot open {:?}: {}", path, e))?;
+    // The OS releases the lock when the file is closed, even after a crash
+    match file.try_lock() {
+        Ok(()) => Ok(Some(InstanceLock { _file: file })),
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Error(e)) => Err(format!("cannot lock {:?}: {}", path, e)),
+    }
+}
+
+/// Listens on the control socket and calls `handler` from a background thread for each
+/// command received. The handler owns the connection and writes the reply with `reply`.
+/// Does nothing if another instance already owns the socket.
+#[cfg(unix)]
+pub fn spawn_server<F>(handler: F)
+where
+    F: Fn(ControlCommand, Stream) + Send + 'static,
+{
+    let path = socket_path();
+    if UnixStream::connect(&path).is_ok() {
+        warn!("Another instance is listening on {:?}; control commands go there.", path);
+        return;
+    }
+    // Left behind by an instance that didn't exit cleanly
+    let _ = std::fs::remove_file(&path);
+
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("Control socket unavailable at {:?}: {}", path, e);
+            return;
+        }
+    };
+    info!("Listening for control commands on {:?}", path);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    error!("Control socket accept failed: {}", e);
+                    continue;
+                }
+            };
+
+            let mut line = String::new();
+            let read = match stream.try_clone() {
+                Ok(reader) => BufReader::new(reader).read_line(&mut line),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = read {
+                error!("Failed to read control command: {}", e);
+                continue;
+            }
+
+            match ControlCommand::parse(line.trim()) {
+                Some(command) => {
+                    debug!("Received control command: {:?}", command);
+                    handler(command, stream);
+                }
+                None => reply(&mut stream, false, &format!("unknown command '{}'", line.trim())),
+            }
+        }
+    });
+}
+
+/// Control commands need a Unix socket, so on Windows there is nothing to listen on.
+#[cfg(not(unix))]
+pub fn spawn_server<F>(_handler: F)
+where
+    F: Fn(ControlCommand, Stream) + Send + 'static,
+{
+    info!("Control commands aren't supported on this platform.");
+}
+
+/// Writes the reply for a command: a status line (`ok` or `error`) followed by `text`.
+pub fn reply(stream: &mut Stream, ok: bool, text: &str) {
+    let status = if ok { "ok" } else { "error" };
+    if let Err(e) = write!(stream, "{}\n{}", status, text) {
+        error!("Failed to reply on control socket: {}", e);
+    }
+}
+
+/// Sends `command` to the running instance and waits for its reply.
+/// Returns the reply text, or an error if the command failed or no instance is running.
+#[cfg(unix)]
+pub fn send(command: ControlCommand) -> Result<String, String> {
+    let path = socket_path();
+    let mut stream = UnixStream::connect(&path)
+        .map_err(|e| format!("no running instance at {:?}: {}", path, e))?;
+    writeln!(stream, "{}", command.as_str()).map_err(|e| e.to_string())?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).map_err(|e| e.to_string())?;
+    match response.split_once('\n') {
+        Some(("ok", text)) => Ok(text.to_string()),
+        Some((_, text)) => Err(text.to_string()),
+        None => Err("the instance closed the connection without replying".to_string()),
+    }
+}
+
+/// Control commands need a Unix socket, so on Windows they always fail.
+#[cfg(not(unix))]
+pub fn send(command: ControlCommand) -> Result<String, String> {
+    Err(format!("'{}' isn't supported on this platform", command.as_str()))
+}
@@ -0,0 +1,115 @@
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{debug, warn};
+use which::which;
+
+use crate::audio;
+
+/// Players tried in order for `sound_cues`.
+#[cfg(target_os = "macos")]
+const PLAYERS: &[&str] = &["afplay"];
+#[cfg(not(target_os = "macos"))]
+const PLAYERS: &[&str] = &["paplay", "pw-play", "aplay"];
+
+/// Longest a cue may hold up the start of a recording.
+const MAX_WAIT: Duration = Duration::from_millis(500);
+
+/// A short tone confirming that recording started or stopped.
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum Cue {
+    /// A high beep.
+    Start,
+    /// A lower one.
+    Stop,
+}
+
+impl Cue {
+    fn frequency(self) -> f32 {
+        match self {
+            Cue::Start => 880.0,
+            Cue::Stop => 440.0,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Cue::Start => "start",
+            Cue::Stop => "stop",
+        }
+    }
+}
+
+/// Plays `cue` on a background thread and calls `then` there once it has finished, so
+/// it isn't picked up by a recording started from `then`. Gives up waiting after half
+/// a second.
+pub fn play_then(cue: Cue, then: impl FnOnce() + Send + 'static) {
+    thread::spawn(move || {
+        let Some(mut child) = spawn_player(cue) else {
+            return then();
+        };
+        let deadline = Instant::now() + MAX_WAIT;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) | Err(_) = child.try_wait() {
+                return then();
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        debug!("Sound cue still playing; not waiting for it.");
+        then();
+        let _ = child.wait();
+    });
+}
+
+/// Plays `cue` without waiting for it.
+pub fn play(cue: Cue) {
+    if let Some(mut child) = spawn_player(cue) {
+        thread::spawn(move || child.wait());
+    }
+}
+
+fn spawn_player(cue: Cue) -> Option<std::process::Child> {
+    let Some(player) = PLAYERS.iter().copied().find(|player| which(player).is_ok()) else {
+        warn!("None of {} found. Cannot play sound cues.", PLAYERS.join(", "));
+        return None;
+    };
+    let path = cue_file(cue)?;
+    Command::new(player)
+        .arg(&path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| warn!("Failed to run {}: {}", player, e))
+        .ok()
+}
+
+/// Writes the cue's WAV to the temp directory the first time it is needed. It is
+/// generated rather than shipped so the binary stays self-contained.
+fn cue_file(cue: Cue) -> Option<PathBuf> {
+    const SAMPLE_RATE: u32 = 22050;
+    const LENGTH: Duration = Duration::from_millis(90);
+
+    let path = std::env::temp_dir().join(format!("whisper-to-input-cue-{}.wav", cue.name()));
+    if path.exists() {
+        return Some(path);
+    }
+    let samples = (SAMPLE_RATE as f32 * LENGTH.as_secs_f32()) as usize;
+    let data: Vec<u8> = (0..samples)
+        .flat_map(|i| {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            // Fade in and out over 10 ms so the tone doesn't click
+            let fade = (t / 0.01).min((LENGTH.as_secs_f32() - t) / 0.01).clamp(0.0, 1.0);
+            let sample = (t * cue.frequency() * std::f32::consts::TAU).sin() * fade * 0.3;
+            ((sample * f32::from(i16::MAX)) as i16).to_le_bytes()
+        })
+        .collect();
+    match audio::write_wav(&path, SAMPLE_RATE, 1, 16, &data) {
+        Ok(()) => Some(path),
+        Err(e) => {
+            warn!("Cannot write sound cue: {}", e);
+            None
+        }
+    }
+}
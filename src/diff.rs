@@ -0,0 +1,51 @@
+/// Produces a word-level diff of `old` against `new` in `git diff --word-diff` style:
+/// removed words are wrapped in `[-...-]` and added words in `{+...+}`.
+pub fn word_diff(old: &str, new: &str) -> String {
+    let old_words: Vec<&str> = old.split_whitespace().collect();
+    let new_words: Vec<&str> = new.split_whitespace().collect();
+
+    // lcs[i][j] = length of the longest common subsequence of old_words[i..] and new_words[j..]
+    let mut lcs = vec![vec![0usize; new_words.len() + 1]; old_words.len() + 1];
+    for i in (0..old_words.len()).rev() {
+        for j in (0..new_words.len()).rev() {
+            lcs[i][j] = if old_words[i] == new_words[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out: Vec<String> = Vec::new();
+    let mut removed: Vec<&str> = Vec::new();
+    let mut added: Vec<&str> = Vec::new();
+    let flush = |out: &mut Vec<String>, removed: &mut Vec<&str>, added: &mut Vec<&str>| {
+        if !removed.is_empty() {
+            out.push(format!("[-{}-]", removed.join(" ")));
+            removed.clear();
+        }
+        if !added.is_empty() {
+            out.push(format!("{{+{}+}}", added.join(" ")));
+            added.clear();
+        }
+    };
+
+    let (mut i, mut j) = (0, 0);
+    while i < old_words.len() || j < new_words.len() {
+        if i < old_words.len() && j < new_words.len() && old_words[i] == new_words[j] {
+            flush(&mut out, &mut removed, &mut added);
+            out.push(old_words[i].to_string());
+            i += 1;
+            j += 1;
+        } else if j < new_words.len() && (i == old_words.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            added.push(new_words[j]);
+            j += 1;
+        } else {
+            removed.push(old_words[i]);
+            i += 1;
+        }
+    }
+    flush(&mut out, &mut removed, &mut added);
+
+    out.join(" ")
+}
@@ -0,0 +1,73 @@
+use which::which;
+
+use crate::config::Config;
+use crate::control;
+
+/// External tools the app shells out to, and what each is used for.
+const TOOLS: &[(&str, &str)] = &[
+    ("arecord", "recording"),
+    ("parecord", "recording without arecord, or from input_source_description"),
+    ("pw-record", "recording without arecord or parecord"),
+    ("pactl", "resolving input_source_description"),
+    ("wl-copy", "clipboard on Wayland without XWayland"),
+    ("xclip", "clipboard without a native one"),
+    ("pbcopy", "clipboard on macOS without a native one"),
+    ("ydotool", "output_mode = \"type\" and auto_paste on Wayland"),
+    ("xdotool", "output_mode = \"type\" and auto_paste on X11"),
+    ("ffmpeg", "recording on macOS and Windows or without the others, large recordings, recording_metadata, converting rejected audio"),
+    ("paplay", "sound_cues (or pw-play, aplay)"),
+    ("ffprobe", "splitting large recordings"),
+    ("ask", "Refine"),
+    ("notify-send", "notifications"),
+    ("secret-tool", "reading the API key from the keyring"),
+    ("zenity", "Save audio"),
+    ("kdialog", "Save audio without zenity"),
+];
+
+/// Prints the version, config and detected tools for `--doctor`, for bug reports.
+/// The API key itself is never printed, and `api_key_command` is not run.
+pub fn print_report() {
+    println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+    println!();
+
+    match Config::path() {
+        Some(path) if path.exists() => println!("Config:   {}", path.display()),
+        Some(path) => println!("Config:   {} (not found, using defaults)", path.display()),
+        None => println!("Config:   no config directory, using defaults"),
+    }
+    let config = Config::load();
+
+    let api = config.primary_provider().api();
+    if api.env_api_key().is_some() {
+        println!("API key:  from {}", api.key_env_var());
+    } else if config.api_key.is_some() {
+        println!("API key:  from api_key in the config");
+    } else if let Some(command) = &config.api_key_command {
+        println!("API key:  from api_key_command '{}'", command);
+    } else {
+        match dirs::config_dir().map(|p| p.join("whisper_api_key")) {
+            Some(path) if path.exists() => println!("API key:  {}", path.display()),
+            Some(path) => println!("API key:  {} (missing)", path.display()),
+            None => println!("API key:  no config directory"),
+        }
+    }
+
+    println!("Providers:");
+    for (i, provider) in config.providers.iter().enumerate() {
+        println!("  {}. {} {} ({})", i + 1, provider.name, provider.model, provider.url);
+    }
+
+    println!("Tools:");
+    for (tool, purpose) in TOOLS {
+        let location = which(tool).map_or("not found".to_string(), |p| p.display().to_string());
+        println!("  {:<9} {:<28} {}", tool, location, purpose);
+    }
+
+    let session = match (std::env::var_os("WAYLAND_DISPLAY"), std::env::var_os("DISPLAY")) {
+        (Some(_), _) => "Wayland (global hotkeys need X11)",
+        (None, Some(_)) => "X11",
+        (None, None) => "no display",
+    };
+    println!("Session:  {}", session);
+    println!("Control:  {}", control::socket_path().display());
+}
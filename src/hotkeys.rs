@@ -0,0 +1,230 @@
+use std::thread;
+
+use log::{info, warn};
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{ConnectionExt, GrabMode, Keycode, ModMask};
+use x11rb::protocol::Event;
+
+/// Something a global hotkey can trigger.
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum HotkeyAction {
+    CopyTranscript,
+    /// Same as pressing Record.
+    ToggleRecording,
+    /// Records while the key is held: pressing it starts recording, releasing it stops.
+    PushToTalk,
+}
+
+/// A parsed key combination such as `Ctrl+Alt+C`.
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub struct Hotkey {
+    modifiers: u16,
+    keysym: u32,
+}
+
+/// Parses a `+`-separated key combination, e.g. `Ctrl+Alt+Space` or `Super+F9`.
+pub fn parse_hotkey(text: &str) -> Result<Hotkey, String> {
+    let mut modifiers = 0u16;
+    let mut keysym = None;
+
+    for part in text.split('+').map(str::trim) {
+        let modifier = match part.to_lowercase().as_str() {
+            "ctrl" | "control" => Some(ModMask::CONTROL),
+            "alt" => Some(ModMask::M1),
+            "shift" => Some(ModMask::SHIFT),
+            "super" | "win" | "meta" => Some(ModMask::M4),
+            _ => None,
+        };
+        match modifier {
+            Some(modifier) => modifiers |= u16::from(modifier),
+            None if keysym.is_none() => keysym = Some(parse_keysym(part)?),
+            None => return Err(format!("Hotkey '{}' has more than one key", text)),
+        }
+    }
+
+    keysym
+        .map(|keysym| Hotkey { modifiers, keysym })
+        .ok_or_else(|| format!("Hotkey '{}' has no key", text))
+}
+
+/// Maps a key name to its X11 keysym.
+fn parse_keysym(name: &str) -> Result<u32, String> {
+    let lower = name.to_lowercase();
+    let mut chars = lower.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii_alphanumeric() {
+            // Latin-1 keysyms match their lowercase ASCII codes
+            return Ok(c as u32);
+        }
+    }
+
+    if let Some(n) = lower.strip_prefix('f').and_then(|n| n.parse::<u32>().ok()) {
+        if (1..=24).contains(&n) {
+            return Ok(0xffbe + n - 1);
+        }
+    }
+
+    match lower.as_str() {
+        "space" => Ok(0x0020),
+        "return" | "enter" => Ok(0xff0d),
+        "escape" | "esc" => Ok(0xff1b),
+        "tab" => Ok(0xff09),
+        "backspace" => Ok(0xff08),
+        "insert" => Ok(0xff63),
+        "delete" => Ok(0xffff),
+        "home" => Ok(0xff50),
+        "end" => Ok(0xff57),
+        "pause" => Ok(0xff13),
+        _ => Err(format!("Unknown key '{}'", name)),
+    }
+}
+
+/// Grabs the given hotkeys on the X11 root window and calls `handler` from a background
+/// thread whenever one is pressed, with `true`, or a `PushToTalk` one is released, with
+/// `false`. Hotkeys that fail to parse or grab are skipped with a warning.
+///
+/// Only works on X11; Wayland compositors don't let clients grab global keys.
+pub fn spawn_listener<F>(bindings: Vec<(String, HotkeyAction)>, handler: F)
+where
+    F: Fn(HotkeyAction, bool) + Send + 'static,
+{
+    if bindings.is_empty() {
+        return;
+    }
+
+    thread::spawn(move || {
+        if let Err(e) = listen(&bindings, handler) {
+            warn!("Global hotkeys unavailable: {}", e);
+        }
+    });
+}
+
+fn listen<F>(bindings: &[(String, HotkeyAction)], handler: F) -> Result<(), String>
+where
+    F: Fn(HotkeyAction, bool),
+{
+    let (conn, screen_num) =
+        x11rb::connect(None).map_err(|e| format!("cannot connect to X11 display: {}", e))?;
+    let root = conn.setup().roots[screen_num].root;
+
+    // Caps Lock and Num Lock (usually Mod2) shouldn't stop a hotkey from matching
+    let lock_masks = [
+        0,
+        u16::from(ModMask::LOCK),
+        u16::from(ModMask::M2),
+        u16::from(ModMask::LOCK) | u16::from(ModMask::M2),
+    ];
+
+    let mut grabbed: Vec<(Keycode, u16, HotkeyAction)> = Vec::new();
+    for (text, action) in bindings {
+        let hotkey = match parse_hotkey(text) {
+            Ok(hotkey) => hotkey,
+            Err(e) => {
+                warn!("Ignoring hotkey for {:?}: {}", action, e);
+                continue;
+            }
+        };
+        let keycode = match keycode_for(&conn, hotkey.keysym)? {
+            Some(keycode) => keycode,
+            None => {
+                warn!("Ignoring hotkey '{}': key not on this keyboard", text);
+                continue;
+            }
+        };
+
+        let mut ok = true;
+        for lock in lock_masks {
+            let result = conn
+                .grab_key(
+                    false,
+                    root,
+                    ModMask::from(hotkey.modifiers | lock),
+                    keycode,
+                    GrabMode::ASYNC,
+                    GrabMode::ASYNC,
+                )
+                .map_err(|e| e.to_string())?
+                .check();
+            if let Err(e) = result {
+                warn!("Failed to grab hotkey '{}' (already in use?): {:?}", text, e);
+                ok = false;
+                break;
+            }
+        }
+        if ok {
+            info!("Registered global hotkey '{}' for {:?}", text, action);
+            grabbed.push((keycode, hotkey.modifiers, *action));
+        }
+    }
+
+    if grabbed.is_empty() {
+        return Ok(());
+    }
+
+    let ignored = u16::from(ModMask::LOCK) | u16::from(ModMask::M2);
+    // The push-to-talk key being held, so its auto-repeat doesn't restart recording
+    let mut held: Option<Keycode> = None;
+    // An event read ahead while checking for auto-repeat
+    let mut pending: Option<Event> = None;
+    loop {
+        let event = match pending.take() {
+            Some(event) => event,
+            None => conn.wait_for_event().map_err(|e| e.to_string())?,
+        };
+        match event {
+            Event::KeyPress(press) => {
+                let state = u16::from(press.state) & !ignored;
+                let action = grabbed
+                    .iter()
+                    .find(|(keycode, modifiers, _)| *keycode == press.detail && *modifiers == state)
+                    .map(|(_, _, action)| *action);
+                match action {
+                    Some(HotkeyAction::PushToTalk) if held == Some(press.detail) => {}
+                    Some(HotkeyAction::PushToTalk) => {
+                        held = Some(press.detail);
+                        handler(HotkeyAction::PushToTalk, true);
+                    }
+                    Some(action) => handler(action, true),
+                    None => {}
+                }
+            }
+            // Modifiers may already be up, so only the key has to match
+            Event::KeyRelease(release) if held == Some(release.detail) => {
+                // While a key is held, X11 repeats it as a release immediately followed
+                // by a press with the same time
+                match conn.poll_for_event().map_err(|e| e.to_string())? {
+                    Some(Event::KeyPress(press))
+                        if press.detail == release.detail && press.time == release.time =>
+                    {
+                        continue;
+                    }
+                    next => pending = next,
+                }
+                held = None;
+                handler(HotkeyAction::PushToTalk, false);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Finds the keycode that produces `keysym` in the current keyboard mapping.
+fn keycode_for(conn: &impl Connection, keysym: u32) -> Result<Option<Keycode>, String> {
+    let setup = conn.setup();
+    let (min, max) = (setup.min_keycode, setup.max_keycode);
+    let mapping = conn
+        .get_keyboard_mapping(min, max - min + 1)
+        .map_err(|e| e.to_string())?
+        .reply()
+        .map_err(|e| e.to_string())?;
+
+    let per_keycode = usize::from(mapping.keysyms_per_keycode);
+    if per_keycode == 0 {
+        return Ok(None);
+    }
+    Ok(mapping
+        .keysyms
+        .chunks(per_keycode)
+        .position(|syms| syms.contains(&keysym))
+        .map(|index| min + index as u8))
+}
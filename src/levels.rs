@@ -0,0 +1,43 @@
+use std::collections::VecDeque;
+
+/// Peak level below which a recording counts as quiet (about -20 dBFS).
+const QUIET_PEAK: f32 = 0.1;
+/// Number of recent recordings the average is taken over.
+const WINDOW: usize = 5;
+/// Recordings needed before a suggestion is made, so one quiet take doesn't trigger it.
+const MIN_SAMPLES: usize = 3;
+
+/// Tracks the peak levels of recent recordings to notice a microphone that is
+/// consistently too quiet, which makes transcriptions noticeably worse.
+#[derive(Default)]
+pub struct LevelTracker {
+    peaks: VecDeque<f32>,
+    /// The suggestion was shown and levels haven't recovered since.
+    suggested: bool,
+}
+
+impl LevelTracker {
+    /// Adds a recording's peak level. Returns a suggestion the first time the rolling
+    /// average drops below the quiet threshold; once levels recover it can be shown again.
+    pub fn record(&mut self, peak: f32) -> Option<String> {
+        if self.peaks.len() == WINDOW {
+            self.peaks.pop_front();
+        }
+        self.peaks.push_back(peak);
+
+        let average = self.peaks.iter().sum::<f32>() / self.peaks.len() as f32;
+        if average >= QUIET_PEAK {
+            self.suggested = false;
+            return None;
+        }
+        if self.suggested || self.peaks.len() < MIN_SAMPLES {
+            return None;
+        }
+
+        self.suggested = true;
+        Some(format!(
+            "Tip: Your recent recordings are very quiet (peak {:.0} dBFS). Try raising the microphone gain.",
+            20.0 * average.max(f32::EPSILON).log10()
+        ))
+    }
+}
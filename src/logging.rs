@@ -0,0 +1,56 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Writes log records to stderr. Messages from this crate are shown from `level` up,
+/// other crates' (slint, reqwest, ...) only from warnings up, so debugging the app
+/// doesn't drown in theirs.
+struct StderrLogger {
+    level: LevelFilter,
+}
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        if metadata.target().starts_with(env!("CARGO_CRATE_NAME")) {
+            metadata.level() <= self.level
+        } else {
+            metadata.level() <= Level::Warn.min(self.level.to_level().unwrap_or(Level::Error))
+        }
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let label = match record.level() {
+            Level::Error => "error",
+            Level::Warn => "warning",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        };
+        eprintln!("{}: {}", label, record.args());
+    }
+
+    fn flush(&self) {}
+}
+
+/// Sets up logging. `RUST_LOG` picks the level (`error`, `warn`, `info`, `debug`,
+/// `trace` or `off`); without it only warnings and errors are shown, or everything
+/// down to debug messages with `verbose`.
+pub fn init(verbose: bool) {
+    let default = if verbose {
+        LevelFilter::Debug
+    } else {
+        LevelFilter::Warn
+    };
+    let level = match std::env::var("RUST_LOG") {
+        Ok(value) => value.trim().parse().unwrap_or_else(|_| {
+            eprintln!("warning: Ignoring RUST_LOG={:?}; expected a level like \"debug\".", value);
+            default
+        }),
+        Err(_) => default,
+    };
+
+    if log::set_boxed_logger(Box::new(StderrLogger { level })).is_ok() {
+        log::set_max_level(level);
+    }
+}
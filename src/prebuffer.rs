@@ -0,0 +1,122 @@
+use std::collections::VecDeque;
+use std::io::Read;
+use std::path::Path;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use log::{debug, error, warn};
+
+use crate::audio;
+use crate::config::Config;
+use crate::recorder::{self, SampleFormat, BITS_PER_SAMPLE};
+use crate::sync::LockExt;
+
+/// Continuously captures audio into a ring buffer holding the last few seconds, so a
+/// recording can start with what was said just before Record was pressed.
+///
+/// The microphone is open for as long as the app runs, which is why this is opt-in.
+#[derive(Clone)]
+pub struct Prebuffer {
+    inner: Arc<Mutex<Inner>>,
+    /// Cleared when the capture process exits.
+    alive: Arc<AtomicBool>,
+    format: SampleFormat,
+}
+
+struct Inner {
+    ring: VecDeque<u8>,
+    capacity: usize,
+    /// Audio captured since Record was pressed, starting with the ring buffer contents.
+    recording: Option<Vec<u8>>,
+}
+
+impl Prebuffer {
+    /// Starts capturing raw audio from `device`, or the default input, and keeps the
+    /// last `seconds` of it.
+    pub fn start(seconds: u32, config: &Config, device: Option<&str>) -> Result<Prebuffer, String> {
+        let mut command = recorder::stream_command(config, device)?;
+        let mut child = command
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("failed to start {:?}: {}", command.get_program(), e))?;
+        let mut stdout = child.stdout.take().ok_or("capture has no stdout")?;
+
+        let format = SampleFormat::from_config(config);
+        let frame = format.frame_bytes();
+        let capacity = seconds as usize * format.sample_rate as usize * frame;
+        let prebuffer = Prebuffer {
+            inner: Arc::new(Mutex::new(Inner {
+                ring: VecDeque::with_capacity(capacity),
+                capacity,
+                recording: None,
+            })),
+            alive: Arc::new(AtomicBool::new(true)),
+            format,
+        };
+
+        let inner = prebuffer.inner.clone();
+        let alive = prebuffer.alive.clone();
+        thread::spawn(move || {
+            let mut buffer = [0u8; 8192];
+            loop {
+                let read = match stdout.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(read) => read,
+                    Err(e) => {
+                        error!("Prebuffer capture failed: {}", e);
+                        break;
+                    }
+                };
+
+                let mut inner = inner.lock_or_recover("prebuffer");
+                let chunk = &buffer[..read];
+                if let Some(recording) = &mut inner.recording {
+                    recording.extend_from_slice(chunk);
+                }
+                inner.ring.extend(chunk);
+                if inner.ring.len() > inner.capacity {
+                    // Drop whole frames so the buffer always starts on a sample boundary
+                    let excess = (inner.ring.len() - inner.capacity).next_multiple_of(frame);
+                    let excess = excess.min(inner.ring.len());
+                    inner.ring.drain(..excess);
+                }
+            }
+            alive.store(false, Ordering::SeqCst);
+            let _ = child.wait();
+            warn!("Prebuffer capture stopped; recordings fall back to the regular recorder.");
+        });
+
+        Ok(prebuffer)
+    }
+
+    /// Starts a recording that begins with the buffered audio. Fails if the capture
+    /// process has stopped, in which case the regular recorder should be used.
+    pub fn begin_recording(&self) -> Result<(), String> {
+        if !self.alive.load(Ordering::SeqCst) {
+            return Err("prebuffer capture is not running".to_string());
+        }
+        let mut inner = self.inner.lock_or_recover("prebuffer");
+        let buffered: Vec<u8> = inner.ring.iter().copied().collect();
+        debug!("Starting recording with {} buffered bytes", buffered.len());
+        inner.recording = Some(buffered);
+        Ok(())
+    }
+
+    /// Ends the current recording without writing it anywhere.
+    pub fn discard_recording(&self) {
+        self.inner.lock_or_recover("prebuffer").recording = None;
+    }
+
+    /// Ends the current recording and writes it to `path` as a WAV file.
+    pub fn finish_recording(&self, path: &Path) -> Result<(), String> {
+        let data = self
+            .inner
+            .lock_or_recover("prebuffer")
+            .recording
+            .take()
+            .ok_or("no prebuffered recording in progress")?;
+        audio::write_wav(path, self.format.sample_rate, self.format.channels, BITS_PER_SAMPLE, &data)
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::BTreeMap;
+use std::sync::{Condvar, Mutex};
+
+use crate::sync::{recover, LockExt};
+
+/// Runs jobs with a bounded number in flight, but hands their results back in the
+/// order the jobs were submitted, so output never arrives out of order.
+pub struct OrderedQueue<T> {
+    state: Mutex<QueueState<T>>,
+    slot_freed: Condvar,
+}
+
+struct QueueState<T> {
+    next_ticket: u64,
+    next_to_deliver: u64,
+    /// Results that finished before an earlier job did.
+    finished: BTreeMap<u64, T>,
+    running: usize,
+}
+
+/// Permission to run one job; the slot is released when this is dropped.
+pub struct Slot<'a, T> {
+    queue: &'a OrderedQueue<T>,
+}
+
+impl<T> OrderedQueue<T> {
+    pub fn new() -> Self {
+        OrderedQueue {
+            state: Mutex::new(QueueState {
+                next_ticket: 0,
+                next_to_deliver: 0,
+                finished: BTreeMap::new(),
+                running: 0,
+            }),
+            slot_freed: Condvar::new(),
+        }
+    }
+
+    /// Registers a job and returns its ticket, which orders its result.
+    pub fn submit(&self) -> u64 {
+        let mut state = self.state.lock_or_recover("queue");
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        ticket
+    }
+
+    /// Jobs submitted whose results haven't been handed back yet.
+    pub fn pending(&self) -> usize {
+        let state = self.state.lock_or_recover("queue");
+        (state.next_ticket - state.next_to_deliver) as usize
+    }
+
+    /// Blocks until fewer than `limit` jobs are running, then takes a slot.
+    pub fn acquire_slot(&self, limit: usize) -> Slot<'_, T> {
+        let mut state = self.state.lock_or_recover("queue");
+        while state.running >= limit.max(1) {
+            state = recover(self.slot_freed.wait(state), "queue");
+        }
+        state.running += 1;
+        Slot { queue: self }
+    }
+
+    /// Records the result for `ticket` and returns every result that is now next in
+    /// line, in submission order. Empty if an earlier job is still running.
+    pub fn complete(&self, ticket: u64, result: T) -> Vec<T> {
+        let mut state = self.state.lock_or_recover("queue");
+        state.finished.insert(ticket, result);
+
+        let mut ready = Vec::new();
+        loop {
+            let next = state.next_to_deliver;
+            match state.finished.remove(&next) {
+                Some(result) => ready.push(result),
+                None => break,
+            }
+            state.next_to_deliver += 1;
+        }
+        ready
+    }
+}
+
+impl<T> Default for OrderedQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for Slot<'_, T> {
+    fn drop(&mut self) {
+        self.queue.state.lock_or_recover("queue").running -= 1;
+        self.queue.slot_freed.notify_one();
+    }
+}
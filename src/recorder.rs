@@ -0,0 +1,569 @@
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{error, info, warn};
+use which::which;
+
+use crate::config::Config;
+use crate::storage;
+
+/// File name prefix of recordings.
+const RECORDING_PREFIX: &str = "whisper_record";
+
+/// Directory recordings are made in: the system temp dir, usually `/tmp`.
+pub fn recording_dir() -> PathBuf {
+    std::env::temp_dir()
+}
+
+/// Returns a fresh path to record to, e.g. `/tmp/whisper_record_1234_1700000000000_0.wav`.
+/// Each recording gets its own file so earlier ones can still be queued for transcription.
+/// The process ID keeps instances apart and the start time keeps a stale file left by a
+/// crashed process with the same ID from being picked up.
+pub fn next_recording_path() -> String {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis());
+    recording_dir()
+        .join(format!(
+            "{}_{}_{}_{}.wav",
+            RECORDING_PREFIX,
+            std::process::id(),
+            started,
+            NEXT_ID.fetch_add(1, Ordering::SeqCst)
+        ))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Audio files this app left on disk, e.g. a recording kept so a failed transcription
+/// can be retried or one kept by `keep_recordings`, with their sizes. Only files named
+/// like the app's recordings are listed.
+pub fn cached_recordings() -> Vec<(PathBuf, u64)> {
+    let dirs = [Some(recording_dir()), storage::recordings_dir()];
+    dirs.into_iter()
+        .flatten()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry.file_name().to_str().is_some_and(|name| {
+                name.starts_with(RECORDING_PREFIX) && name.ends_with(".wav")
+            })
+        })
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            metadata.is_file().then(|| (entry.path(), metadata.len()))
+        })
+        .collect()
+}
+
+/// Recordings are always 16-bit PCM; the rate and channels come from the config.
+pub const BITS_PER_SAMPLE: u16 = 16;
+
+/// Sample rate and channel count recordings are made with.
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub struct SampleFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl SampleFormat {
+    pub fn from_config(config: &Config) -> SampleFormat {
+        SampleFormat { sample_rate: config.sample_rate, channels: config.channels }
+    }
+
+    /// Bytes in one sample of every channel.
+    pub fn frame_bytes(self) -> usize {
+        usize::from(self.channels) * usize::from(BITS_PER_SAMPLE / 8)
+    }
+
+    /// Arguments that set this format for `backend`, apart from the container.
+    fn args(self, backend: RecordBackend) -> Vec<String> {
+        let (rate, channels) = (self.sample_rate, self.channels);
+        match backend {
+            RecordBackend::Arecord => vec![
+                "-f".to_string(),
+                "S16_LE".to_string(),
+                "-r".to_string(),
+                rate.to_string(),
+                "-c".to_string(),
+                channels.to_string(),
+            ],
+            RecordBackend::Parecord => vec![
+                "--format=s16le".to_string(),
+                format!("--rate={}", rate),
+                format!("--channels={}", channels),
+            ],
+            RecordBackend::PwRecord => vec![
+                "--format=s16".to_string(),
+                format!("--rate={}", rate),
+                format!("--channels={}", channels),
+            ],
+            RecordBackend::Ffmpeg => {
+                vec!["-ac".to_string(), channels.to_string(), "-ar".to_string(), rate.to_string()]
+            }
+        }
+    }
+}
+
+/// A command-line recorder that can capture from the default input device.
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+// Only ffmpeg is used on macOS and Windows
+#[cfg_attr(any(target_os = "macos", target_os = "windows"), allow(dead_code))]
+pub enum RecordBackend {
+    /// ALSA's `arecord`.
+    Arecord,
+    /// PulseAudio's `parecord`, also provided by pipewire-pulse.
+    Parecord,
+    /// PipeWire's `pw-record`.
+    PwRecord,
+    /// `ffmpeg`, capturing with AVFoundation on macOS, DirectShow on Windows and
+    /// PulseAudio elsewhere.
+    Ffmpeg,
+}
+
+/// ffmpeg input format for capturing on this platform, and the prefix it needs before
+/// a device name: AVFoundation takes `[video]:[audio]` and DirectShow `audio=<name>`.
+#[cfg(target_os = "macos")]
+const FFMPEG_CAPTURE: (&str, &str) = ("avfoundation", ":");
+#[cfg(target_os = "windows")]
+const FFMPEG_CAPTURE: (&str, &str) = ("dshow", "audio=");
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const FFMPEG_CAPTURE: (&str, &str) = ("pulse", "");
+
+impl RecordBackend {
+    /// All backends available on this platform, in the order `detect_record_backend`
+    /// tries them.
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    pub const ALL: &[RecordBackend] = &[RecordBackend::Ffmpeg];
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    pub const ALL: &[RecordBackend] = &[
+        RecordBackend::Arecord,
+        RecordBackend::Parecord,
+        RecordBackend::PwRecord,
+        RecordBackend::Ffmpeg,
+    ];
+
+    pub fn program(self) -> &'static str {
+        match self {
+            RecordBackend::Arecord => "arecord",
+            RecordBackend::Parecord => "parecord",
+            RecordBackend::PwRecord => "pw-record",
+            RecordBackend::Ffmpeg => "ffmpeg",
+        }
+    }
+
+    /// Arguments that record a WAV file in `format` to `path`. They go after
+    /// `input_args`.
+    pub fn args(self, path: &str, format: SampleFormat) -> Vec<String> {
+        let container: &[&str] = match self {
+            // You might need to adjust the device (-D hw:...) depending on your system
+            RecordBackend::Arecord => &["-t", "wav", /*"-D", "hw:0,0",*/ "-q"],
+            RecordBackend::Parecord => &["--file-format=wav"],
+            RecordBackend::PwRecord => &[],
+            RecordBackend::Ffmpeg => &["-c:a", "pcm_s16le", "-y"],
+        };
+        format
+            .args(self)
+            .into_iter()
+            .chain(container.iter().copied().chain([path]).map(String::from))
+            .collect()
+    }
+
+    /// Arguments that pick the device to record from: `id`, or the default one.
+    pub fn input_args(self, id: Option<&str>) -> Vec<String> {
+        match (self, id) {
+            (RecordBackend::Ffmpeg, id) => {
+                let (format, prefix) = FFMPEG_CAPTURE;
+                // -nostdin: ffmpeg would otherwise read keystrokes from the terminal. On
+                // Windows stdin is how it is told to stop, see `stop_on_stdin`.
+                let stdin: &[&str] = if cfg!(target_os = "windows") { &[] } else { &["-nostdin"] };
+                stdin
+                    .iter()
+                    .chain(&["-hide_banner", "-loglevel", "error", "-f", format, "-i"])
+                    .map(|arg| arg.to_string())
+                    .chain([format!("{}{}", prefix, id.unwrap_or("default"))])
+                    .collect()
+            }
+            (_, None) => Vec::new(),
+            (RecordBackend::Arecord, Some(id)) => vec!["-D".to_string(), id.to_string()],
+            (RecordBackend::Parecord, Some(id)) => vec![format!("--device={}", id)],
+            (RecordBackend::PwRecord, Some(id)) => vec!["--target".to_string(), id.to_string()],
+        }
+    }
+
+    /// Arguments that stream raw samples in `format` to stdout. They go after
+    /// `input_args`.
+    pub fn stream_args(self, format: SampleFormat) -> Vec<String> {
+        let container: &[&str] = match self {
+            RecordBackend::Arecord => &["-t", "raw", "-q"],
+            RecordBackend::Parecord => &["--raw"],
+            RecordBackend::PwRecord => &["--raw", "-"],
+            RecordBackend::Ffmpeg => &["-f", "s16le", "-"],
+        };
+        format.args(self).into_iter().chain(container.iter().copied().map(String::from)).collect()
+    }
+}
+
+/// Returns the first installed recorder of `RecordBackend::ALL`, or an error naming
+/// the ones searched for.
+pub fn detect_record_backend() -> Result<RecordBackend, String> {
+    RecordBackend::ALL
+        .iter()
+        .copied()
+        .find(|backend| which(backend.program()).is_ok())
+        .ok_or_else(|| {
+            let searched: Vec<&str> = RecordBackend::ALL.iter().map(|b| b.program()).collect();
+            if cfg!(target_os = "macos") {
+                error!("No recording tool found. Please install ffmpeg (e.g., brew install ffmpeg)");
+            } else if cfg!(target_os = "windows") {
+                error!("No recording tool found. Please install ffmpeg (e.g., winget install ffmpeg)");
+            } else {
+                error!("No recording tool found. Please install one (e.g., sudo apt install alsa-utils)");
+            }
+            format!("Error: no recording tool found (searched {})", searched.join(", "))
+        })
+}
+
+/// An input device the recorder can capture from.
+#[derive(Clone, Debug)]
+pub struct AudioDevice {
+    /// Device name passed to the recorder, e.g. `hw:CARD=Yeti,DEV=0` or a PulseAudio source.
+    pub id: String,
+    /// Human-readable name shown in the device list.
+    pub description: String,
+}
+
+/// Input devices of the recorder `detect_record_backend` picks: `arecord -L` for
+/// arecord, ffmpeg's AVFoundation or DirectShow device list on macOS or Windows,
+/// `pactl list sources` otherwise. Empty if they can't be listed.
+pub fn list_input_devices() -> Vec<AudioDevice> {
+    let (program, args): (&str, &[&str]) = match detect_record_backend() {
+        Ok(RecordBackend::Arecord) => ("arecord", &["-L"]),
+        Ok(RecordBackend::Ffmpeg) if cfg!(target_os = "macos") => (
+            "ffmpeg",
+            &["-hide_banner", "-f", "avfoundation", "-list_devices", "true", "-i", ""],
+        ),
+        Ok(RecordBackend::Ffmpeg) if cfg!(target_os = "windows") => (
+            "ffmpeg",
+            &["-hide_banner", "-f", "dshow", "-list_devices", "true", "-i", "dummy"],
+        ),
+        Ok(_) => ("pactl", &["list", "sources"]),
+        Err(_) => return Vec::new(),
+    };
+    let output = match Command::new(program).args(args).output() {
+        // Listing devices makes ffmpeg fail for lack of an input, and prints to stderr
+        Ok(output) if program == "ffmpeg" => {
+            let output = String::from_utf8_lossy(&output.stderr);
+            return if cfg!(target_os = "windows") {
+                parse_dshow_devices(&output)
+            } else {
+                parse_avfoundation_devices(&output)
+            };
+        }
+        Ok(output) if output.status.success() => output,
+        Ok(output) => {
+            error!("{} {} exited with status: {}", program, args.join(" "), output.status);
+            return Vec::new();
+        }
+        Err(e) => {
+            error!("Failed to run {}: {}", program, e);
+            return Vec::new();
+        }
+    };
+
+    let output = String::from_utf8_lossy(&output.stdout);
+    if program == "arecord" {
+        parse_arecord_devices(&output)
+    } else {
+        parse_pactl_sources(&output)
+            .into_iter()
+            .filter(|(name, _)| !name.ends_with(".monitor"))
+            .map(|(id, description)| AudioDevice { id, description })
+            .collect()
+    }
+}
+
+/// Builds the command that records to `path`, from `device` if one was picked.
+/// Returns the command plus an optional warning to show the user, or an error
+/// if no usable recorder is installed.
+pub fn recorder_command(
+    config: &Config,
+    path: &str,
+    device: Option<&str>,
+) -> Result<(Command, Option<String>), String> {
+    let format = SampleFormat::from_config(config);
+    if let Some(device) = device {
+        let backend = detect_record_backend()?;
+        info!("Recording with {} from {}", backend.program(), device);
+        let mut command = Command::new(backend.program());
+        command.args(backend.input_args(Some(device))).args(backend.args(path, format));
+        stop_on_stdin(&mut command);
+        return Ok((command, None));
+    }
+
+    let (source, warning) = select_source(config);
+    if let Some(source) = source {
+        let mut command = Command::new("parecord");
+        command
+            .arg(format!("--device={}", source))
+            .args(RecordBackend::Parecord.args(path, format));
+        return Ok((command, None));
+    }
+
+    let backend = detect_record_backend()?;
+    info!("Recording with {}", backend.program());
+    let mut command = Command::new(backend.program());
+    command
+        .args(backend.input_args(default_input_device().as_deref()))
+        .args(backend.args(path, format));
+    stop_on_stdin(&mut command);
+    Ok((command, warning))
+}
+
+/// The device to record from when none was picked. DirectShow has no default device,
+/// so on Windows it is the first one listed; elsewhere the recorder picks.
+fn default_input_device() -> Option<String> {
+    if cfg!(target_os = "windows") {
+        list_input_devices().into_iter().next().map(|device| device.id)
+    } else {
+        None
+    }
+}
+
+/// On Windows the recorder can't be sent SIGTERM, so ffmpeg is stopped by writing `q`
+/// to its stdin instead, which also finalizes the WAV header.
+fn stop_on_stdin(command: &mut Command) {
+    if cfg!(target_os = "windows") {
+        command.stdin(Stdio::piped());
+    }
+}
+
+/// Builds a command that streams raw samples in the recording format to stdout,
+/// from the same device `recorder_command` would record from.
+pub fn stream_command(config: &Config, device: Option<&str>) -> Result<Command, String> {
+    let format = SampleFormat::from_config(config);
+    if let Some(device) = device {
+        let backend = detect_record_backend()?;
+        let mut command = Command::new(backend.program());
+        command.args(backend.input_args(Some(device))).args(backend.stream_args(format));
+        stop_on_stdin(&mut command);
+        return Ok(command);
+    }
+
+    if let (Some(source), _) = select_source(config) {
+        let mut command = Command::new("parecord");
+        command
+            .arg(format!("--device={}", source))
+            .args(RecordBackend::Parecord.stream_args(format));
+        return Ok(command);
+    }
+
+    let backend = detect_record_backend()?;
+    let mut command = Command::new(backend.program());
+    command
+        .args(backend.input_args(default_input_device().as_deref()))
+        .args(backend.stream_args(format));
+    stop_on_stdin(&mut command);
+    Ok(command)
+}
+
+/// Resolves `input_source_description` to a PulseAudio/PipeWire source. Returns the source,
+/// or `None` plus a warning to show the user when the default device has to be used instead.
+fn select_source(config: &Config) -> (Option<String>, Option<String>) {
+    let description = match &config.input_source_description {
+        Some(description) => description,
+        None => return (None, None),
+    };
+
+    if which("parecord").is_err() || which("pactl").is_err() {
+        warn!("input_source_description requires 'parecord' and 'pactl'. Falling back to the default device.");
+        return (None, Some("parecord/pactl missing, using default input".to_string()));
+    }
+
+    match resolve_pulse_source(description) {
+        Some(source) => {
+            info!("Resolved input '{}' to source '{}'", description, source);
+            (Some(source), None)
+        }
+        None => {
+            warn!(
+                "No input source matches '{}'. Falling back to the default device.",
+                description
+            );
+            (None, Some(format!("'{}' not found, using default input", description)))
+        }
+    }
+}
+
+/// Finds the PulseAudio/PipeWire source name whose description matches `description`.
+/// An exact (case-insensitive) match wins; otherwise the first non-monitor source whose
+/// description contains it is used.
+fn resolve_pulse_source(description: &str) -> Option<String> {
+    let output = match Command::new("pactl").args(["list", "sources"]).output() {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => {
+            error!("pactl list sources exited with status: {}", output.status);
+            return None;
+        }
+        Err(e) => {
+            error!("Failed to run pactl: {}", e);
+            return None;
+        }
+    };
+
+    let sources = parse_pactl_sources(&String::from_utf8_lossy(&output.stdout));
+    let wanted = description.to_lowercase();
+
+    sources
+        .iter()
+        .find(|(_, desc)| desc.to_lowercase() == wanted)
+        .or_else(|| {
+            sources.iter().find(|(name, desc)| {
+                !name.ends_with(".monitor") && desc.to_lowercase().contains(&wanted)
+            })
+        })
+        .map(|(name, _)| name.clone())
+}
+
+/// Parses the audio devices from `ffmpeg -f avfoundation -list_devices true -i ""`,
+/// which lists video devices, then audio devices, as `[AVFoundation indev @ 0x...] [0] Name`.
+/// The index is the device ID.
+fn parse_avfoundation_devices(output: &str) -> Vec<AudioDevice> {
+    output
+        .lines()
+        .skip_while(|line| !line.contains("audio devices:"))
+        .skip(1)
+        .filter_map(|line| {
+            // The first bracket is the log prefix, the second the device index
+            let rest = line.split_once("] [")?.1;
+            let (index, name) = rest.split_once("] ")?;
+            index.parse::<u32>().ok()?;
+            Some(AudioDevice {
+                id: index.to_string(),
+                description: name.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Parses the audio devices from `ffmpeg -f dshow -list_devices true -i dummy`. Newer
+/// ffmpeg marks each device, as `[dshow @ 0x...] "Name" (audio)`; older versions list
+/// them under a `DirectShow audio devices` heading instead. The name is the device ID.
+/// Alternative names are skipped.
+fn parse_dshow_devices(output: &str) -> Vec<AudioDevice> {
+    let mut audio_section = false;
+    let mut devices = Vec::new();
+
+    for line in output.lines() {
+        if line.contains("DirectShow audio devices") {
+            audio_section = true;
+            continue;
+        }
+        if line.contains("DirectShow video devices") {
+            audio_section = false;
+            continue;
+        }
+        let Some(rest) = line.split_once("] ").map(|(_, rest)| rest.trim()) else {
+            continue;
+        };
+        let Some((name, kind)) = rest.strip_prefix('"').and_then(|rest| rest.split_once('"')) else {
+            continue;
+        };
+        let kind = kind.trim();
+        if kind.contains("audio") || (kind.is_empty() && audio_section) {
+            devices.push(AudioDevice { id: name.to_string(), description: name.to_string() });
+        }
+    }
+
+    devices
+}
+
+/// Parses `arecord -L` output: each device name is on its own line, followed by indented
+/// description lines. The `null` device is skipped.
+fn parse_arecord_devices(output: &str) -> Vec<AudioDevice> {
+    let mut devices: Vec<AudioDevice> = Vec::new();
+
+    for line in output.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if !line.starts_with(char::is_whitespace) {
+            devices.push(AudioDevice {
+                id: line.trim().to_string(),
+                description: String::new(),
+            });
+        } else if let Some(device) = devices.last_mut() {
+            // Descriptions span up to two lines: card name, then device name
+            if device.description.is_empty() {
+                device.description = line.trim().to_string();
+            } else if !device.description.contains(", ") {
+                device.description = format!("{}, {}", device.description, line.trim());
+            }
+        }
+    }
+
+    devices.retain(|device| device.id != "null");
+    for device in &mut devices {
+        if device.description.is_empty() {
+            device.description = device.id.clone();
+        }
+    }
+    devices
+}
+
+/// Parses `pactl list sources` output into `(name, description)` pairs.
+fn parse_pactl_sources(output: &str) -> Vec<(String, String)> {
+    let mut sources = Vec::new();
+    let mut name: Option<String> = None;
+
+    for line in output.lines().map(str::trim) {
+        if let Some(value) = line.strip_prefix("Name:") {
+            name = Some(value.trim().to_string());
+        } else if let Some(value) = line.strip_prefix("Description:") {
+            if let Some(name) = name.take() {
+                sources.push((name, value.trim().to_string()));
+            }
+        }
+    }
+
+    sources
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(devices: Vec<AudioDevice>) -> Vec<String> {
+        devices.into_iter().map(|device| device.id).collect()
+    }
+
+    #[test]
+    fn dshow_devices_marked_as_audio() {
+        let output = r#"[dshow @ 000001] "Integrated Camera" (video)
+[dshow @ 000001]   Alternative name "@device_pnp_\\?\usb#vid_0001"
+[dshow @ 000001] "Microphone (Realtek(R) Audio)" (audio)
+[dshow @ 000001]   Alternative name "@device_cm_{33D9A762}\wave_{1}"
+[dshow @ 000001] "Headset (USB Audio)" (audio)
+dummy: Immediate exit requested"#;
+        assert_eq!(
+            ids(parse_dshow_devices(output)),
+            ["Microphone (Realtek(R) Audio)", "Headset (USB Audio)"]
+        );
+    }
+
+    #[test]
+    fn dshow_devices_under_the_audio_heading() {
+        let output = r#"[dshow @ 000001] DirectShow video devices (some may be both video and audio devices)
+[dshow @ 000001]  "Integrated Camera"
+[dshow @ 000001]     Alternative name "@device_pnp_\\?\usb#vid_0001"
+[dshow @ 000001] DirectShow audio devices
+[dshow @ 000001]  "Microphone (Realtek Audio)"
+[dshow @ 000001]     Alternative name "@device_cm_{33D9A762}\wave_{1}"
+dummy: Immediate exit requested"#;
+        assert_eq!(ids(parse_dshow_devices(output)), ["Microphone (Realtek Audio)"]);
+    }
+}
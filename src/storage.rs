@@ -0,0 +1,271 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use chrono::{DateTime, Local};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use which::which;
+
+use crate::text::Word;
+
+/// Directory for state the app keeps between runs, e.g. `~/.local/state/whisper-to-input`.
+fn state_dir() -> Option<PathBuf> {
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|p| p.join("whisper-to-input"))
+}
+
+fn last_transcript_path() -> Option<PathBuf> {
+    state_dir().map(|p| p.join("last_transcript.txt"))
+}
+
+fn last_words_path() -> Option<PathBuf> {
+    state_dir().map(|p| p.join("last_words.json"))
+}
+
+fn input_device_path() -> Option<PathBuf> {
+    state_dir().map(|p| p.join("input_device.txt"))
+}
+
+fn window_size_path() -> Option<PathBuf> {
+    state_dir().map(|p| p.join("window_size.txt"))
+}
+
+/// Saves the transcript so it can be restored on the next start. Failures are only logged.
+pub fn save_last_transcript(text: &str) {
+    if let Some(path) = last_transcript_path() {
+        write_state(&path, text);
+    }
+}
+
+/// Returns the transcript saved by the previous run, if any.
+pub fn load_last_transcript() -> Option<String> {
+    let text = std::fs::read_to_string(last_transcript_path()?).ok()?;
+    if text.trim().is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+/// Saves the word timestamps of the last transcript as a JSON array, for `timestamps`.
+/// Failures are only logged.
+pub fn save_last_words(words: &[Word]) {
+    let path = match last_words_path() {
+        Some(path) => path,
+        None => return,
+    };
+    match serde_json::to_string_pretty(words) {
+        Ok(json) => write_state(&path, &json),
+        Err(e) => warn!("Failed to serialize word timestamps: {}", e),
+    }
+}
+
+/// Remembers the input device picked in the window; `None` goes back to the default.
+pub fn save_input_device(id: Option<&str>) {
+    if let Some(path) = input_device_path() {
+        write_state(&path, id.unwrap_or_default());
+    }
+}
+
+/// Returns the input device picked in a previous run, if any.
+pub fn load_input_device() -> Option<String> {
+    let id = std::fs::read_to_string(input_device_path()?).ok()?;
+    let id = id.trim();
+    (!id.is_empty()).then(|| id.to_string())
+}
+
+/// Remembers the main window's size in logical pixels, to reopen it at that size.
+pub fn save_window_size(width: f32, height: f32) {
+    if let Some(path) = window_size_path() {
+        write_state(&path, &format!("{} {}", width.round(), height.round()));
+    }
+}
+
+/// Returns the window size saved by the previous run, if any.
+pub fn load_window_size() -> Option<(f32, f32)> {
+    let text = std::fs::read_to_string(window_size_path()?).ok()?;
+    let (width, height) = text.trim().split_once(' ')?;
+    let size = (width.parse().ok()?, height.parse().ok()?);
+    (size.0 > 0.0 && size.1 > 0.0).then_some(size)
+}
+
+/// Writes a state file, creating the state directory. Failures are only logged.
+fn write_state(path: &Path, text: &str) {
+    if let Some(dir) = path.parent() {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            warn!("Failed to create state directory {:?}: {}", dir, e);
+            return;
+        }
+    }
+
+    if let Err(e) = std::fs::write(path, text) {
+        warn!("Failed to save {:?}: {}", path, e);
+    }
+}
+
+/// A past transcript, one per line of the history file.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HistoryEntry {
+    pub timestamp: DateTime<Local>,
+    pub text: String,
+}
+
+fn history_path() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|p| p.join("whisper-to-input").join("history.jsonl"))
+}
+
+/// Adds a transcript to the history, dropping the oldest entries beyond `max_entries`.
+/// Failures are only logged.
+pub fn append_history(text: &str, max_entries: usize) {
+    let path = match history_path() {
+        Some(path) => path,
+        None => return,
+    };
+
+    let mut entries = load_history();
+    entries.push(HistoryEntry {
+        timestamp: Local::now(),
+        text: text.to_string(),
+    });
+    let excess = entries.len().saturating_sub(max_entries);
+    entries.drain(..excess);
+
+    let mut lines = String::new();
+    for entry in &entries {
+        match serde_json::to_string(entry) {
+            Ok(line) => {
+                lines.push_str(&line);
+                lines.push('\n');
+            }
+            Err(e) => warn!("Failed to serialize history entry: {}", e),
+        }
+    }
+    write_state(&path, &lines);
+}
+
+/// Returns the saved transcripts, oldest first. Unreadable lines are skipped.
+pub fn load_history() -> Vec<HistoryEntry> {
+    let contents = match history_path().map(std::fs::read_to_string) {
+        Some(Ok(contents)) => contents,
+        _ => return Vec::new(),
+    };
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                warn!("Skipping unreadable history entry: {}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Directory that `keep_recordings` moves transcribed recordings to,
+/// e.g. `~/.local/share/whisper-to-input/recordings`.
+pub fn recordings_dir() -> Option<PathBuf> {
+    dirs::data_local_dir().map(|p| p.join("whisper-to-input").join("recordings"))
+}
+
+/// Details about a recording, written into its file name and tags when it is kept.
+pub struct RecordingMetadata {
+    pub captured_at: DateTime<Local>,
+    pub device: String,
+    pub model: String,
+    /// First line of the transcript.
+    pub summary: String,
+}
+
+/// Moves a transcribed recording into `dir`. With metadata, the file is named after
+/// its capture time and model and, with `tag` and `ffmpeg` installed, tagged with the
+/// details; otherwise it keeps its original name. Returns where it ended up; failures
+/// are only logged.
+pub fn keep_recording(
+    path: &Path,
+    dir: &Path,
+    metadata: Option<&RecordingMetadata>,
+    tag: bool,
+) -> Option<PathBuf> {
+    if let Err(e) = std::fs::create_dir_all(dir) {
+        warn!("Failed to create recordings directory {:?}: {}", dir, e);
+        return None;
+    }
+
+    let file_name = match metadata {
+        Some(metadata) => format!(
+            "whisper_record_{}_{}.wav",
+            metadata.captured_at.format("%Y-%m-%d_%H-%M-%S"),
+            sanitize(&metadata.model)
+        ),
+        None => path.file_name()?.to_string_lossy().into_owned(),
+    };
+    let destination = dir.join(file_name);
+
+    if let Some(metadata) = metadata.filter(|_| tag) {
+        if tag_recording(path, &destination, metadata) {
+            if destination != path {
+                let _ = std::fs::remove_file(path);
+            }
+            info!("Kept tagged recording at {:?}", destination);
+            return Some(destination);
+        }
+    }
+
+    // rename fails across filesystems, e.g. from a tmpfs /tmp
+    let moved = std::fs::rename(path, &destination).or_else(|_| {
+        std::fs::copy(path, &destination).and_then(|_| std::fs::remove_file(path))
+    });
+    match moved {
+        Ok(()) => {
+            info!("Kept recording at {:?}", destination);
+            Some(destination)
+        }
+        Err(e) => {
+            warn!("Failed to keep recording {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// Copies `source` to `destination` with metadata tags using ffmpeg. Returns whether
+/// it worked; a missing ffmpeg is skipped silently.
+fn tag_recording(source: &Path, destination: &Path, metadata: &RecordingMetadata) -> bool {
+    if which("ffmpeg").is_err() {
+        return false;
+    }
+
+    let status = Command::new("ffmpeg")
+        .args(["-v", "error", "-y", "-i"])
+        .arg(source)
+        .args(["-c", "copy"])
+        .arg("-metadata")
+        .arg(format!("date={}", metadata.captured_at.to_rfc3339()))
+        .arg("-metadata")
+        .arg(format!("artist={}", metadata.device))
+        .arg("-metadata")
+        .arg(format!("title=Transcribed with {}", metadata.model))
+        .arg("-metadata")
+        .arg(format!("comment={}", metadata.summary))
+        .arg(destination)
+        .status();
+    match status {
+        Ok(status) if status.success() => true,
+        Ok(status) => {
+            warn!("ffmpeg failed to tag recording ({})", status);
+            false
+        }
+        Err(e) => {
+            warn!("Failed to run ffmpeg: {}", e);
+            false
+        }
+    }
+}
+
+/// Makes `text` safe to use in a file name.
+fn sanitize(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+        .collect()
+}
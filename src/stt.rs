@@ -0,0 +1,275 @@
+use reqwest::blocking::{multipart, Body, Client, RequestBuilder};
+
+use crate::audio;
+use crate::config::{Config, Provider, ResponseFormat, MODEL, OPENAI_BASE_URL, PROVIDER, TRANSCRIPTIONS_PATH};
+use crate::{parse_transcription, send_request, Progress, Transcription, UploadError};
+
+/// What `SttProvider::transcribe` sends a file to, and how.
+pub struct SttOptions<'a> {
+    pub provider: &'a Provider,
+    /// The provider's transcription endpoint, or its translation endpoint.
+    pub url: &'a str,
+    pub model: &'a str,
+    /// Spoken language, or `None` to have it detected.
+    pub language: Option<&'a str>,
+    /// Empty for self-hosted servers that run without authentication.
+    pub api_key: &'a str,
+    pub config: &'a Config,
+    pub progress: &'a Progress<'a>,
+}
+
+/// A hosted speech-to-text API, which each kind of `Provider` is called with.
+pub trait SttProvider: Sync {
+    /// Name shown in the status line.
+    fn name(&self) -> &'static str;
+
+    /// Base URL of the hosted API.
+    fn base_url(&self) -> &'static str;
+
+    /// Path of the transcription endpoint below the base URL.
+    fn transcriptions_path(&self) -> &'static str;
+
+    /// Transcription model used unless another is configured.
+    fn default_model(&self) -> &'static str;
+
+    /// Environment variable holding an API key for this provider. It is only ever sent
+    /// to providers of this kind.
+    fn key_env_var(&self) -> &'static str;
+
+    /// Value of the `Authorization` header for `api_key`.
+    fn authorization(&self, api_key: &str) -> String;
+
+    /// Published price per minute of audio for `model`, in dollars. `None` for models
+    /// this app doesn't know the price of, e.g. on a self-hosted server.
+    fn price_per_minute(&self, model: &str) -> Option<f64>;
+
+    /// Sends the file to `options.url`, retrying transient failures.
+    fn transcribe(&self, file_path: &str, options: &SttOptions) -> Result<Transcription, UploadError>;
+
+    /// The key in `key_env_var`, if it is set and not blank.
+    fn env_api_key(&self) -> Option<String> {
+        std::env::var(self.key_env_var())
+            .ok()
+            .map(|key| key.trim().to_string())
+            .filter(|key| !key.is_empty())
+    }
+}
+
+/// OpenAI, or any server with an OpenAI-compatible API.
+pub struct OpenAi;
+
+impl SttProvider for OpenAi {
+    fn name(&self) -> &'static str {
+        PROVIDER
+    }
+
+    fn base_url(&self) -> &'static str {
+        OPENAI_BASE_URL
+    }
+
+    fn transcriptions_path(&self) -> &'static str {
+        TRANSCRIPTIONS_PATH
+    }
+
+    fn default_model(&self) -> &'static str {
+        MODEL
+    }
+
+    fn key_env_var(&self) -> &'static str {
+        "OPENAI_API_KEY"
+    }
+
+    fn authorization(&self, api_key: &str) -> String {
+        format!("Bearer {}", api_key)
+    }
+
+    fn price_per_minute(&self, model: &str) -> Option<f64> {
+        match model {
+            "whisper-1" | "gpt-4o-transcribe" => Some(0.006),
+            "gpt-4o-mini-transcribe" => Some(0.003),
+            _ => None,
+        }
+    }
+
+    fn transcribe(&self, file_path: &str, options: &SttOptions) -> Result<Transcription, UploadError> {
+        // Only the gpt-4o transcription models stream, and only plain text or JSON
+        let stream = options.config.stream_transcripts
+            && options.model.starts_with("gpt-4o")
+            && options.url.ends_with("/transcriptions")
+            && matches!(options.config.request_format(), ResponseFormat::Text | ResponseFormat::Json);
+        send_multipart(self, file_path, options, stream)
+    }
+}
+
+/// Groq's OpenAI-compatible Whisper API.
+pub struct Groq;
+
+impl SttProvider for Groq {
+    fn name(&self) -> &'static str {
+        "Groq"
+    }
+
+    fn base_url(&self) -> &'static str {
+        "https://api.groq.com/openai/v1"
+    }
+
+    fn transcriptions_path(&self) -> &'static str {
+        TRANSCRIPTIONS_PATH
+    }
+
+    fn default_model(&self) -> &'static str {
+        "whisper-large-v3-turbo"
+    }
+
+    fn key_env_var(&self) -> &'static str {
+        "GROQ_API_KEY"
+    }
+
+    fn authorization(&self, api_key: &str) -> String {
+        format!("Bearer {}", api_key)
+    }
+
+    fn price_per_minute(&self, model: &str) -> Option<f64> {
+        // Groq bills by the hour
+        match model {
+            "whisper-large-v3-turbo" => Some(0.04 / 60.0),
+            "whisper-large-v3" => Some(0.111 / 60.0),
+            _ => None,
+        }
+    }
+
+    fn transcribe(&self, file_path: &str, options: &SttOptions) -> Result<Transcription, UploadError> {
+        send_multipart(self, file_path, options, false)
+    }
+}
+
+/// Deepgram's `/listen` API, which takes the audio as the request body.
+pub struct Deepgram;
+
+impl SttProvider for Deepgram {
+    fn name(&self) -> &'static str {
+        "Deepgram"
+    }
+
+    fn base_url(&self) -> &'static str {
+        "https://api.deepgram.com/v1"
+    }
+
+    fn transcriptions_path(&self) -> &'static str {
+        "/listen"
+    }
+
+    fn default_model(&self) -> &'static str {
+        "nova-2"
+    }
+
+    fn key_env_var(&self) -> &'static str {
+        "DEEPGRAM_API_KEY"
+    }
+
+    fn authorization(&self, api_key: &str) -> String {
+        format!("Token {}", api_key)
+    }
+
+    fn price_per_minute(&self, model: &str) -> Option<f64> {
+        match model {
+            "nova-2" | "nova-3" => Some(0.0043),
+            _ => None,
+        }
+    }
+
+    fn transcribe(&self, file_path: &str, options: &SttOptions) -> Result<Transcription, UploadError> {
+        let build = |client: &Client, sent| {
+            audio::file_body(file_path, options.config, sent)
+                .map(|body| deepgram_request(client, body, options))
+        };
+        send_request(self, options, false, build, |body, _| parse_deepgram(body))
+    }
+}
+
+/// Sends the file as a multipart upload to an OpenAI-compatible endpoint, asking for
+/// the transcript to be streamed if `stream` is set.
+fn send_multipart(
+    api: &dyn SttProvider,
+    file_path: &str,
+    options: &SttOptions,
+    stream: bool,
+) -> Result<Transcription, UploadError> {
+    let build = |client: &Client, sent| {
+        audio::file_part(file_path, options.config, sent)
+            .map(|file| openai_request(client, file, options, stream))
+    };
+    send_request(api, options, stream, build, parse_transcription)
+}
+
+/// A multipart upload to an OpenAI-compatible `/audio/transcriptions` or
+/// `/audio/translations` endpoint.
+fn openai_request(
+    client: &Client,
+    file: multipart::Part,
+    options: &SttOptions,
+    stream: bool,
+) -> RequestBuilder {
+    let config = options.config;
+    let mut form = multipart::Form::new()
+        .part("file", file)
+        .text("response_format", config.request_format().as_str())
+        .text("model", options.model.to_string());
+    if let Some(language) = options.language {
+        form = form.text("language", language.to_string());
+    }
+    if let Some(prompt) = &config.prompt {
+        form = form.text("prompt", prompt.clone());
+    }
+    if stream {
+        form = form.text("stream", "true");
+    }
+    // Translations don't take granularities
+    if config.timestamps && options.url.ends_with("/transcriptions") {
+        form = form.text("timestamp_granularities[]", "word");
+    }
+    client.post(options.url).multipart(form)
+}
+
+/// A request to Deepgram's `/listen` endpoint, which takes the audio as the body and
+/// the options as query parameters.
+fn deepgram_request(client: &Client, (body, mime): (Body, String), options: &SttOptions) -> RequestBuilder {
+    let mut query = vec![("model", options.model), ("smart_format", "true")];
+    match options.language {
+        Some(language) => query.push(("language", language)),
+        None => query.push(("detect_language", "true")),
+    }
+    client
+        .post(options.url)
+        .query(&query)
+        .header(reqwest::header::CONTENT_TYPE, mime)
+        .body(body)
+}
+
+/// Extracts the transcript, word timestamps and duration from a Deepgram response,
+/// leaving the provider details empty.
+fn parse_deepgram(body: &str) -> Result<Transcription, String> {
+    let value: serde_json::Value = serde_json::from_str(body)
+        .map_err(|e| format!("Failed to parse JSON response: {}", e))?;
+    let alternative = value
+        .pointer("/results/channels/0/alternatives/0")
+        .ok_or_else(|| "Deepgram response has no transcript".to_string())?;
+    let text = alternative
+        .get("transcript")
+        .and_then(|t| t.as_str())
+        .ok_or_else(|| "Deepgram response has no 'transcript' field".to_string())?;
+    // Its words carry the same word, start and end as verbose_json's, plus a confidence
+    let words = alternative
+        .get("words")
+        .and_then(|words| serde_json::from_value(words.clone()).ok())
+        .unwrap_or_default();
+    Ok(Transcription {
+        text: text.to_string(),
+        provider: String::new(),
+        model: String::new(),
+        language_retry: None,
+        raw_response: None,
+        words,
+        duration: value.pointer("/metadata/duration").and_then(|d| d.as_f64()),
+    })
+}
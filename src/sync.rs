@@ -0,0 +1,30 @@
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use log::warn;
+
+/// Locking that survives a panic in another thread. A thread that panics while holding
+/// a lock poisons it, and `lock().expect(..)` would then take the whole app down with
+/// it; the data behind these locks is simple enough to keep using as it was left.
+pub trait LockExt<T> {
+    /// Locks the mutex, recovering it if it was poisoned. `site` names the caller in
+    /// the warning logged when that happens.
+    fn lock_or_recover(&self, site: &str) -> MutexGuard<'_, T>;
+}
+
+impl<T> LockExt<T> for Mutex<T> {
+    fn lock_or_recover(&self, site: &str) -> MutexGuard<'_, T> {
+        self.lock().unwrap_or_else(|poisoned| {
+            warn!("A thread panicked holding the lock used on {}; continuing.", site);
+            self.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+}
+
+/// Takes the guard back from a poisoned `Condvar::wait`, as `lock_or_recover` does.
+pub fn recover<G>(result: Result<G, PoisonError<G>>, site: &str) -> G {
+    result.unwrap_or_else(|poisoned| {
+        warn!("A thread panicked holding the lock used on {}; continuing.", site);
+        poisoned.into_inner()
+    })
+}
@@ -0,0 +1,237 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Config, ResponseFormat, SegmentOutput};
+
+/// Abbreviations that end in a period without ending a sentence.
+const ABBREVIATIONS: &[&str] = &[
+    "e.g.", "i.e.", "vs.", "cf.", "approx.", "mr.", "mrs.", "ms.", "dr.", "prof.", "sr.", "jr.",
+    "st.", "no.", "fig.", "inc.", "ltd.", "co.",
+];
+
+/// A word of a transcript and when it was said, in seconds from the start of the recording.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Word {
+    pub word: String,
+    pub start: f64,
+    pub end: f64,
+}
+
+/// Applies the configured post-processing to a transcript before it is shown and copied.
+/// Subtitle formats are left untouched since their layout is meaningful.
+pub fn postprocess(text: &str, config: &Config) -> String {
+    if matches!(config.request_format(), ResponseFormat::Srt | ResponseFormat::Vtt) {
+        return text.to_string();
+    }
+
+    let mut text = text.to_string();
+    if config.strip_trailing_newline {
+        text = strip_trailing_newline(&text);
+    }
+    if config.trim_whitespace {
+        text = text.trim().to_string();
+    }
+    if config.collapse_spaces {
+        text = collapse_spaces(&text);
+    }
+    if config.capitalize_sentences {
+        text = capitalize_sentences(&text);
+    }
+
+    match config.segment_output {
+        SegmentOutput::None => text,
+        SegmentOutput::Sentences => split_sentences(&text).join("\n"),
+    }
+}
+
+/// Removes one trailing line break, `\n` or `\r\n`.
+pub fn strip_trailing_newline(text: &str) -> String {
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    text.strip_suffix('\r').unwrap_or(text).to_string()
+}
+
+/// Replaces each run of spaces and tabs with a single space, keeping line breaks.
+pub fn collapse_spaces(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut in_run = false;
+    for c in text.chars() {
+        if c == ' ' || c == '\t' {
+            if !in_run {
+                collapsed.push(' ');
+            }
+            in_run = true;
+        } else {
+            collapsed.push(c);
+            in_run = false;
+        }
+    }
+    collapsed
+}
+
+/// Uppercases the first letter of the text and of every word that follows the end of
+/// a sentence, as `split_sentences` finds them. Opening quotes and brackets are skipped.
+pub fn capitalize_sentences(text: &str) -> String {
+    let mut capitalized = String::with_capacity(text.len());
+    let mut at_start = true;
+    for piece in text.split_inclusive(char::is_whitespace) {
+        let word = piece.trim_end();
+        if word.is_empty() {
+            capitalized.push_str(piece);
+            continue;
+        }
+        if at_start {
+            let mut chars = piece.chars();
+            for c in chars.by_ref() {
+                if c.is_alphabetic() {
+                    capitalized.extend(c.to_uppercase());
+                    break;
+                }
+                capitalized.push(c);
+            }
+            capitalized.push_str(chars.as_str());
+        } else {
+            capitalized.push_str(piece);
+        }
+        at_start = ends_sentence(word);
+    }
+    capitalized
+}
+
+/// Adds `next` to the end of `previous` with `separator` in between. The separator is
+/// left out at the start and when `previous` already ends with it.
+pub fn append(previous: &str, separator: &str, next: &str) -> String {
+    let next = next.trim_start();
+    if previous.trim().is_empty() {
+        return next.to_string();
+    }
+
+    let mut text = previous.to_string();
+    // Whitespace separators count as present when the text ends with any whitespace
+    let has_separator = text.ends_with(separator)
+        || (separator.trim().is_empty() && text.ends_with(char::is_whitespace));
+    if !has_separator {
+        // A separator like "\n- " replaces trailing spaces rather than following them
+        if separator.starts_with(char::is_whitespace) {
+            text.truncate(text.trim_end().len());
+        }
+        text.push_str(separator);
+    }
+    text.push_str(next);
+    text
+}
+
+/// Splits text into sentences at `.`, `!`, `?` and `…` followed by a new sentence,
+/// without splitting after common abbreviations, initials, or inside numbers like `3.5`.
+pub fn split_sentences(text: &str) -> Vec<String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut sentences = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+
+    for (i, word) in words.iter().enumerate() {
+        current.push(word);
+        let next = words.get(i + 1);
+        if ends_sentence(word) && next.is_none_or(|next| starts_sentence(next)) {
+            sentences.push(current.join(" "));
+            current.clear();
+        }
+    }
+    if !current.is_empty() {
+        sentences.push(current.join(" "));
+    }
+
+    sentences
+}
+
+/// Whether `word` ends with sentence-final punctuation that isn't part of an abbreviation.
+fn ends_sentence(word: &str) -> bool {
+    // Closing quotes and brackets can follow the punctuation: `"Stop!"`
+    let trimmed = word.trim_end_matches(['"', '\'', ')', ']', '”', '’']);
+    if trimmed.ends_with(['!', '?', '…']) {
+        return true;
+    }
+    if !trimmed.ends_with('.') {
+        return false;
+    }
+
+    let lower = trimmed.to_lowercase();
+    let body = &lower[..lower.len() - 1];
+    let is_initial = body.chars().count() == 1 && body.chars().all(char::is_alphabetic);
+    // Dotted acronyms like "U.S." contain a period before the final one
+    let is_dotted = body.contains('.') && !body.ends_with('.');
+    !(ABBREVIATIONS.contains(&lower.as_str()) || is_initial || is_dotted)
+}
+
+/// Whether `word` looks like the start of a new sentence.
+fn starts_sentence(word: &str) -> bool {
+    word.trim_start_matches(['"', '\'', '(', '[', '“', '‘', '¿', '¡'])
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_uppercase() || c.is_numeric())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strip_trailing_newline_removes_one_line_break() {
+        assert_eq!(strip_trailing_newline("Hello\n"), "Hello");
+        assert_eq!(strip_trailing_newline("Hello\r\n"), "Hello");
+        assert_eq!(strip_trailing_newline("Hello\n\n"), "Hello\n");
+        assert_eq!(strip_trailing_newline("Hello"), "Hello");
+        assert_eq!(strip_trailing_newline(""), "");
+        assert_eq!(strip_trailing_newline("Grüße\n"), "Grüße");
+    }
+
+    #[test]
+    fn collapse_spaces_keeps_line_breaks() {
+        assert_eq!(collapse_spaces("a  b\t\tc"), "a b c");
+        assert_eq!(collapse_spaces("a \t b"), "a b");
+        assert_eq!(collapse_spaces("a\n\nb"), "a\n\nb");
+        assert_eq!(collapse_spaces("  a  "), " a ");
+        assert_eq!(collapse_spaces(""), "");
+        assert_eq!(collapse_spaces("日本語  テキスト"), "日本語 テキスト");
+    }
+
+    #[test]
+    fn capitalize_sentences_after_sentence_ends() {
+        assert_eq!(capitalize_sentences("hello. how are you? fine!"), "Hello. How are you? Fine!");
+        assert_eq!(capitalize_sentences("see e.g. the docs. then go"), "See e.g. the docs. Then go");
+        assert_eq!(capitalize_sentences("he said \"stop.\" \"why?\""), "He said \"stop.\" \"Why?\"");
+        assert_eq!(capitalize_sentences("it costs 3.5 euros"), "It costs 3.5 euros");
+        assert_eq!(capitalize_sentences("  leading space"), "  Leading space");
+        assert_eq!(capitalize_sentences(""), "");
+    }
+
+    #[test]
+    fn capitalize_sentences_handles_unicode() {
+        assert_eq!(capitalize_sentences("élan vital. über alles"), "Élan vital. Über alles");
+        assert_eq!(capitalize_sentences("¿qué tal? bien…"), "¿Qué tal? Bien…");
+        // Scripts without case are left alone
+        assert_eq!(capitalize_sentences("日本語です。"), "日本語です。");
+    }
+
+    #[test]
+    fn postprocess_applies_enabled_transforms() {
+        let config = Config {
+            strip_trailing_newline: true,
+            trim_whitespace: true,
+            collapse_spaces: true,
+            capitalize_sentences: true,
+            ..Config::default()
+        };
+        assert_eq!(postprocess("  hello   world.  bye\n", &config), "Hello world. Bye");
+        assert_eq!(postprocess("", &config), "");
+        assert_eq!(postprocess("  as is\n", &Config::default()), "  as is\n");
+    }
+
+    #[test]
+    fn trim_whitespace_trims_both_ends() {
+        let config = Config {
+            trim_whitespace: true,
+            ..Config::default()
+        };
+        assert_eq!(postprocess(" \t hi there \n", &config), "hi there");
+        assert_eq!(postprocess("   ", &config), "");
+        assert_eq!(postprocess("\u{3000}全角\u{3000}", &config), "全角");
+    }
+}
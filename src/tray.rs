@@ -0,0 +1,330 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use log::{debug, info};
+use zbus::blocking::connection::Builder;
+use zbus::blocking::Connection;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Type, Value};
+use zbus::{interface, SignalContext};
+
+const ITEM_PATH: &str = "/StatusNotifierItem";
+const MENU_PATH: &str = "/MenuBar";
+
+/// Menu item IDs; 0 is the menu itself.
+const RECORD_ITEM: i32 = 1;
+const SHOW_ITEM: i32 = 2;
+const SEPARATOR_ITEM: i32 = 3;
+const QUIT_ITEM: i32 = 4;
+
+/// Something picked from the tray icon or its menu.
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum TrayAction {
+    /// Same as pressing Record.
+    ToggleRecording,
+    ShowWindow,
+    Quit,
+}
+
+/// What the icon shows.
+#[derive(Eq, PartialEq, Clone, Copy, Debug)]
+pub enum TrayState {
+    Idle,
+    Recording,
+    Processing,
+}
+
+impl TrayState {
+    /// Freedesktop icon theme name.
+    fn icon_name(self) -> &'static str {
+        match self {
+            TrayState::Idle => "audio-input-microphone",
+            TrayState::Recording => "media-record",
+            TrayState::Processing => "emblem-synchronizing",
+        }
+    }
+
+    fn tooltip(self) -> &'static str {
+        match self {
+            TrayState::Idle => "Idle",
+            TrayState::Recording => "Recording",
+            TrayState::Processing => "Transcribing",
+        }
+    }
+}
+
+type Handler = Arc<dyn Fn(TrayAction) + Send + Sync>;
+
+/// Icon name, pixmaps (width, height, ARGB data), title and text.
+type ToolTip = (String, Vec<(i32, i32, Vec<u8>)>, String, String);
+
+/// The tray icon, kept alive for as long as it should be shown.
+#[derive(Clone)]
+pub struct Tray {
+    connection: Connection,
+}
+
+/// Shows a tray icon with Record/Stop, Show window and Quit, over the
+/// StatusNotifierItem D-Bus protocol that KDE, XFCE and GNOME (with the AppIndicator
+/// extension) implement. Clicking the icon shows the window. `handler` is called on a
+/// D-Bus thread.
+pub fn start<F>(handler: F) -> Result<Tray, String>
+where
+    F: Fn(TrayAction) + Send + Sync + 'static,
+{
+    let handler: Handler = Arc::new(handler);
+    let name = format!("org.kde.StatusNotifierItem-{}-1", std::process::id());
+    let connection = Builder::session()
+        .and_then(|builder| builder.name(name.as_str()))
+        .and_then(|builder| {
+            builder.serve_at(
+                ITEM_PATH,
+                StatusNotifierItem { state: TrayState::Idle, handler: handler.clone() },
+            )
+        })
+        .and_then(|builder| {
+            builder.serve_at(MENU_PATH, DbusMenu { state: TrayState::Idle, revision: 1, handler })
+        })
+        .and_then(|builder| builder.build())
+        .map_err(|e| format!("cannot connect to the session bus: {}", e))?;
+
+    connection
+        .call_method(
+            Some("org.kde.StatusNotifierWatcher"),
+            "/StatusNotifierWatcher",
+            Some("org.kde.StatusNotifierWatcher"),
+            "RegisterStatusNotifierItem",
+            &(name.as_str(),),
+        )
+        .map_err(|e| format!("no tray to show the icon in ({})", e))?;
+    info!("Tray icon registered as {}", name);
+    Ok(Tray { connection })
+}
+
+impl Tray {
+    /// Swaps the icon and the Record/Stop label to match `state`.
+    pub fn set_state(&self, state: TrayState) {
+        debug!("Tray state: {:?}", state);
+        let server = self.connection.object_server();
+        if let Ok(item) = server.interface::<_, StatusNotifierItem>(ITEM_PATH) {
+            item.get_mut().state = state;
+            let _ = zbus::block_on(StatusNotifierItem::new_icon(item.signal_context()));
+            let _ = zbus::block_on(StatusNotifierItem::new_tool_tip(item.signal_context()));
+        }
+        if let Ok(menu) = server.interface::<_, DbusMenu>(MENU_PATH) {
+            let revision = {
+                let mut menu = menu.get_mut();
+                menu.state = state;
+                menu.revision += 1;
+                menu.revision
+            };
+            let _ = zbus::block_on(DbusMenu::layout_updated(menu.signal_context(), revision, 0));
+        }
+    }
+}
+
+/// The icon itself, `org.kde.StatusNotifierItem`.
+struct StatusNotifierItem {
+    state: TrayState,
+    handler: Handler,
+}
+
+#[interface(name = "org.kde.StatusNotifierItem")]
+impl StatusNotifierItem {
+    #[zbus(property)]
+    fn category(&self) -> &str {
+        "ApplicationStatus"
+    }
+
+    #[zbus(property)]
+    fn id(&self) -> &str {
+        env!("CARGO_PKG_NAME")
+    }
+
+    #[zbus(property)]
+    fn title(&self) -> &str {
+        "Whisper to Input"
+    }
+
+    #[zbus(property)]
+    fn status(&self) -> &str {
+        "Active"
+    }
+
+    #[zbus(property)]
+    fn icon_name(&self) -> &str {
+        self.state.icon_name()
+    }
+
+    #[zbus(property)]
+    fn tool_tip(&self) -> ToolTip {
+        (
+            self.state.icon_name().to_string(),
+            Vec::new(),
+            "Whisper to Input".to_string(),
+            self.state.tooltip().to_string(),
+        )
+    }
+
+    #[zbus(property)]
+    fn item_is_menu(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn menu(&self) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(MENU_PATH).expect("Menu path is valid")
+    }
+
+    /// Left click.
+    fn activate(&self, _x: i32, _y: i32) {
+        (self.handler)(TrayAction::ShowWindow);
+    }
+
+    /// Middle click.
+    fn secondary_activate(&self, _x: i32, _y: i32) {
+        (self.handler)(TrayAction::ToggleRecording);
+    }
+
+    fn scroll(&self, _delta: i32, _orientation: &str) {}
+
+    #[zbus(signal)]
+    async fn new_icon(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn new_tool_tip(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+}
+
+/// A menu entry and its children, as `com.canonical.dbusmenu` lays them out.
+#[derive(serde::Serialize, Type)]
+struct Layout {
+    id: i32,
+    properties: HashMap<String, OwnedValue>,
+    children: Vec<OwnedValue>,
+}
+
+/// The icon's menu, `com.canonical.dbusmenu`.
+struct DbusMenu {
+    state: TrayState,
+    /// Bumped whenever the labels change, so the tray fetches the layout again.
+    revision: u32,
+    handler: Handler,
+}
+
+impl DbusMenu {
+    fn item_properties(&self, id: i32) -> HashMap<String, OwnedValue> {
+        let mut properties = HashMap::new();
+        let mut set = |key: &str, value: Value| {
+            if let Ok(value) = OwnedValue::try_from(value) {
+                properties.insert(key.to_string(), value);
+            }
+        };
+        match id {
+            0 => set("children-display", Value::from("submenu")),
+            RECORD_ITEM => {
+                let label = if self.state == TrayState::Recording { "Stop" } else { "Record" };
+                set("label", Value::from(label));
+            }
+            SHOW_ITEM => set("label", Value::from("Show window")),
+            SEPARATOR_ITEM => set("type", Value::from("separator")),
+            QUIT_ITEM => set("label", Value::from("Quit")),
+            _ => {}
+        }
+        properties
+    }
+
+    fn item_layout(&self, id: i32) -> Layout {
+        let children = if id == 0 {
+            [RECORD_ITEM, SHOW_ITEM, SEPARATOR_ITEM, QUIT_ITEM]
+                .into_iter()
+                .filter_map(|child| {
+                    let layout = self.item_layout(child);
+                    let structure = zbus::zvariant::StructureBuilder::new()
+                        .add_field(layout.id)
+                        .add_field(layout.properties)
+                        .add_field(layout.children)
+                        .build();
+                    OwnedValue::try_from(Value::from(structure)).ok()
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        Layout { id, properties: self.item_properties(id), children }
+    }
+}
+
+#[interface(name = "com.canonical.dbusmenu")]
+impl DbusMenu {
+    #[zbus(property)]
+    fn version(&self) -> u32 {
+        3
+    }
+
+    #[zbus(property)]
+    fn text_direction(&self) -> &str {
+        "ltr"
+    }
+
+    #[zbus(property)]
+    fn status(&self) -> &str {
+        "normal"
+    }
+
+    #[zbus(property)]
+    fn icon_theme_path(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn get_layout(
+        &self,
+        parent_id: i32,
+        _recursion_depth: i32,
+        _property_names: Vec<String>,
+    ) -> (u32, Layout) {
+        (self.revision, self.item_layout(parent_id))
+    }
+
+    fn get_group_properties(
+        &self,
+        ids: Vec<i32>,
+        _property_names: Vec<String>,
+    ) -> Vec<(i32, HashMap<String, OwnedValue>)> {
+        ids.into_iter().map(|id| (id, self.item_properties(id))).collect()
+    }
+
+    fn get_property(&self, id: i32, name: &str) -> zbus::fdo::Result<OwnedValue> {
+        self.item_properties(id)
+            .remove(name)
+            .ok_or_else(|| zbus::fdo::Error::InvalidArgs(format!("No property {}", name)))
+    }
+
+    fn event(&self, id: i32, event_id: &str, _data: Value<'_>, _timestamp: u32) {
+        if event_id != "clicked" {
+            return;
+        }
+        match id {
+            RECORD_ITEM => (self.handler)(TrayAction::ToggleRecording),
+            SHOW_ITEM => (self.handler)(TrayAction::ShowWindow),
+            QUIT_ITEM => (self.handler)(TrayAction::Quit),
+            _ => {}
+        }
+    }
+
+    fn event_group(&self, events: Vec<(i32, String, Value<'_>, u32)>) -> Vec<i32> {
+        for (id, event_id, data, timestamp) in events {
+            self.event(id, &event_id, data, timestamp);
+        }
+        Vec::new()
+    }
+
+    fn about_to_show(&self, _id: i32) -> bool {
+        false
+    }
+
+    fn about_to_show_group(&self, _ids: Vec<i32>) -> (Vec<i32>, Vec<i32>) {
+        (Vec::new(), Vec::new())
+    }
+
+    #[zbus(signal)]
+    async fn layout_updated(ctxt: &SignalContext<'_>, revision: u32, parent: i32) -> zbus::Result<()>;
+}